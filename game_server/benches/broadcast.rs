@@ -0,0 +1,156 @@
+//! Measures how fast a single game actor fans messages out to a large game.
+//!
+//! Run with `cargo bench --bench broadcast`. Every round the host shows a slide and every player answers it, which
+//! closes the round and reveals the answers. Commands go through the game's mailbox and every client has its own
+//! queue read by its own task, like the websockets of a host-paced classroom game.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use gudritis_axum::{
+    actors::{GameActorHandle, GameManagerActorHandle},
+    config::LimitsConfig,
+    game::{GameMode, GameSettings, Payload, Player, StoredGameSession},
+    metrics,
+    storage::InMemoryRepository,
+};
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, Notify};
+
+const PLAYERS: usize = 1000;
+const ROUNDS: usize = 20;
+
+fn stored_session() -> StoredGameSession {
+    let slides: Vec<_> = (0..ROUNDS)
+        .map(|index| {
+            json!({
+                "duration": 30,
+                "text": format!("Question {index}"),
+                "is_multiple_answer": false,
+                "answers": [
+                    { "index": 0, "text": "Answer 1" },
+                    { "index": 1, "text": "Answer 2" },
+                    { "index": 2, "text": "Answer 3" },
+                    { "index": 3, "text": "Answer 4" },
+                ],
+                "correct_answer": [index % 4],
+            })
+        })
+        .collect();
+    let mut session: Value =
+        serde_json::from_str(include_str!("../fixtures/stored_session.json")).unwrap();
+    session["template"]["slides"] = slides.into();
+    serde_json::from_value(session).unwrap()
+}
+
+/// What the clients have been sent, counted by the tasks reading their queues.
+#[derive(Default)]
+struct Clients {
+    messages: AtomicUsize,
+    reveals: AtomicUsize,
+    revealed: Notify,
+}
+
+impl Clients {
+    /// Reads everything a client is sent, like a websocket keeping up with the game would.
+    fn connect(self: &Arc<Self>, buffer: usize) -> mpsc::Sender<Payload> {
+        let (tx, mut rx) = mpsc::channel::<Payload>(buffer);
+        let clients = self.clone();
+        tokio::spawn(async move {
+            while let Some(payload) = rx.recv().await {
+                clients.messages.fetch_add(1, Ordering::Relaxed);
+                if payload.as_str().contains(r#""method":"Reveal""#) {
+                    clients.reveals.fetch_add(1, Ordering::AcqRel);
+                    clients.revealed.notify_one();
+                }
+            }
+        });
+        tx
+    }
+
+    /// Waits until the given number of clients have been sent a reveal.
+    async fn wait_for_reveals(&self, reveals: usize) {
+        while self.reveals.load(Ordering::Acquire) < reveals {
+            self.revealed.notified().await;
+        }
+    }
+}
+
+/// Hosts the game through the game manager and connects the host and every player to it.
+async fn setup(clients: &Arc<Clients>, limits: LimitsConfig) -> GameActorHandle {
+    let repository = Arc::new(InMemoryRepository::default());
+    repository.insert_session(stored_session());
+    let mut game_manager = GameManagerActorHandle::new(repository, limits).await;
+    let settings = GameSettings {
+        mode: GameMode::HostPaced,
+        ..GameSettings::default()
+    };
+    let code = game_manager
+        .host_game("session".to_string(), settings)
+        .await
+        .unwrap();
+    let game = game_manager.find_game(code).unwrap();
+
+    let host = Player::new(
+        "host".to_string(),
+        "Host".to_string(),
+        Some(clients.connect(limits.client_buffer)),
+    );
+    assert!(game.join_game(host).await);
+    for index in 0..PLAYERS {
+        let player_id = format!("player{index}");
+        let tx = clients.connect(limits.client_buffer);
+        assert!(
+            game.join_game(Player::new(player_id.clone(), player_id, Some(tx)))
+                .await
+        );
+    }
+    game
+}
+
+#[tokio::main]
+async fn main() {
+    let clients = Arc::new(Clients::default());
+    let game = setup(&clients, LimitsConfig::default()).await;
+    // Let the joins' messages and the lobby's player list arrive before timing anything
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let joined_messages = clients.messages.load(Ordering::Relaxed);
+
+    let start = Instant::now();
+    game.start_game("host".to_string()).await;
+    for round in 0..ROUNDS {
+        if round > 0 {
+            game.next_slide("host".to_string()).await;
+        }
+        for index in 0..PLAYERS {
+            game.submit_answer(
+                round,
+                vec![index % 4],
+                Instant::now(),
+                format!("player{index}"),
+            )
+            .await;
+        }
+        // The last answer closes the round, and the round is done once everyone has seen the reveal
+        clients.wait_for_reveals((round + 1) * (PLAYERS + 1)).await;
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    let messages = clients.messages.load(Ordering::Relaxed) - joined_messages;
+    // Every client kept up with the game on the default queue size
+    assert_eq!(0, metrics::slow_consumers());
+
+    println!(
+        "{PLAYERS} players, {ROUNDS} rounds in {:.1} ms",
+        seconds * 1000.0
+    );
+    println!("{:.0} rounds/s", ROUNDS as f64 / seconds);
+    println!(
+        "{:.0} messages/s ({messages} messages)",
+        messages as f64 / seconds
+    );
+}
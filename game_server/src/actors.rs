@@ -1,22 +1,81 @@
-use crate::game::{GameSession, Player, StoredGameSession};
-use mongodb::{bson::doc, Database};
-use std::{collections::HashMap, time::Instant};
-use tokio::sync::{mpsc, oneshot};
+use crate::config::LimitsConfig;
+use crate::game::{ConnectionId, GameSession, GameSettings, Player};
+use crate::storage::{GameRepository, StorageError};
+use futures_util::future::join_all;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::MissedTickBehavior,
+};
+
+/// How often a game sends out its leaderboard while scores keep changing.
+const LEADERBOARD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often a game tells everyone about the players who joined since the last update.
+const LOBBY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Reasons a game session could not be hosted.
+#[derive(Debug)]
+pub enum HostError {
+    /// No stored game session exists with the given id.
+    NotFound,
+    /// The session is already running on this server.
+    AlreadyHosted,
+    /// Another running session already uses the same game code.
+    DuplicateCode(u32),
+    /// The session's template can't be played.
+    InvalidTemplate(String),
+    /// The storage backend could not be read.
+    Storage(StorageError),
+    /// The server is shutting down and no longer takes new games.
+    ShuttingDown,
+    /// The game manager has stopped, so nothing can take the request.
+    Unavailable,
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostError::NotFound => write!(f, "game session not found"),
+            HostError::AlreadyHosted => write!(f, "game session is already hosted"),
+            HostError::DuplicateCode(code) => write!(f, "game code {code} is already in use"),
+            HostError::InvalidTemplate(reason) => write!(f, "invalid game template: {reason}"),
+            HostError::Storage(err) => write!(f, "storage error: {err}"),
+            HostError::ShuttingDown => write!(f, "server is shutting down"),
+            HostError::Unavailable => write!(f, "game manager has stopped"),
+        }
+    }
+}
+
+impl std::error::Error for HostError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HostError::Storage(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 enum LobbyMessage {
     Host {
         session_id: String,
-    },
-    Join {
-        game_code: u32,
-        player: Player,
-        respond_to: oneshot::Sender<Option<GameActorHandle>>,
+        settings: GameSettings,
+        respond_to: oneshot::Sender<Result<u32, HostError>>,
     },
 }
 
 enum GameMessage {
     Join {
         player: Player,
+        respond_to: oneshot::Sender<bool>,
     },
     Start {
         player_id: String,
@@ -27,8 +86,15 @@ enum GameMessage {
         answer_time: Instant,
         slide_index: usize,
     },
+    Next {
+        player_id: String,
+    },
+    Close {
+        player_id: String,
+    },
     Leave {
         player_id: String,
+        connection_id: ConnectionId,
     },
     Kick {
         kick_player_id: String,
@@ -37,95 +103,251 @@ enum GameMessage {
     End {
         player_id: String,
     },
+    /// Saves the game and stops the actor, acknowledging once the game is saved.
+    Shutdown {
+        respond_to: oneshot::Sender<()>,
+    },
+}
+
+/// The running games by game code.
+///
+/// Shared between the game manager, which registers hosted games, the game actors, which remove themselves once
+/// they end, and every join, which looks games up without waiting on the game manager.
+#[derive(Clone, Default)]
+struct GameRegistry {
+    games: Arc<RwLock<HashMap<u32, GameActorHandle>>>,
+    /// Set once the server starts shutting down, after which no games are hosted or joined.
+    closed: Arc<AtomicBool>,
+}
+
+impl GameRegistry {
+    fn get(&self, game_code: u32) -> Option<GameActorHandle> {
+        self.games.read().unwrap().get(&game_code).cloned()
+    }
+
+    fn is_hosted(&self, session_id: &str) -> bool {
+        self.games
+            .read()
+            .unwrap()
+            .values()
+            .any(|game| game.session_id == session_id)
+    }
+
+    /// Registers a game unless its code is already taken or the registry has been closed.
+    fn insert(&self, game_code: u32, game: GameActorHandle) -> Result<(), HostError> {
+        let mut games = self.games.write().unwrap();
+        if self.is_closed() {
+            return Err(HostError::ShuttingDown);
+        }
+        if games.contains_key(&game_code) {
+            return Err(HostError::DuplicateCode(game_code));
+        }
+        games.insert(game_code, game);
+        Ok(())
+    }
+
+    /// Removes a game, unless the code has since been taken by another session.
+    fn remove(&self, game_code: u32, session_id: &str) {
+        let mut games = self.games.write().unwrap();
+        if games
+            .get(&game_code)
+            .is_some_and(|game| game.session_id == session_id)
+        {
+            games.remove(&game_code);
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Stops registering games and takes out every running one, so they can no longer be found or joined.
+    fn close(&self) -> Vec<GameActorHandle> {
+        let mut games = self.games.write().unwrap();
+        self.closed.store(true, Ordering::Release);
+        games.drain().map(|(_, game)| game).collect()
+    }
+
+    /// Looks up a running game. A game whose actor has stopped is dropped from the registry instead.
+    fn find(&self, game_code: u32) -> Option<GameActorHandle> {
+        let game = self.get(game_code)?;
+        if game.sender.is_closed() {
+            self.remove(game_code, &game.session_id);
+            return None;
+        }
+        Some(game)
+    }
 }
 
 #[derive(Clone)]
 pub struct GameManagerActorHandle {
     sender: mpsc::Sender<LobbyMessage>,
+    registry: GameRegistry,
 }
 
 impl GameManagerActorHandle {
-    pub async fn new(db: Database) -> Self {
-        let (sender, receiver) = mpsc::channel(32);
+    pub async fn new(repository: Arc<dyn GameRepository>, limits: LimitsConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(limits.manager_mailbox);
+        let registry = GameRegistry::default();
 
-        let actor = GameManagerActor::new(receiver, db);
+        let actor = GameManagerActor::new(receiver, registry.clone(), repository, limits);
 
         tokio::spawn(run_game_manager_actor(actor));
 
-        Self { sender }
+        Self { sender, registry }
     }
 
-    pub async fn host_game(&mut self, session_id: String) {
-        println!("Hosting game with id:{session_id}");
-        let msg = LobbyMessage::Host { session_id };
-        let _ = self.sender.send(msg).await;
-    }
-
-    pub async fn join_game(&mut self, player: Player, game_code: u32) -> Option<GameActorHandle> {
+    /// Loads the stored game session and spawns a game actor for it, returning the game code players join with.
+    pub async fn host_game(
+        &mut self,
+        session_id: String,
+        settings: GameSettings,
+    ) -> Result<u32, HostError> {
         let (send, recv) = oneshot::channel();
-        let msg = LobbyMessage::Join {
-            player,
-            game_code,
+        let msg = LobbyMessage::Host {
+            session_id,
+            settings,
             respond_to: send,
         };
 
-        let _ = self.sender.send(msg).await;
-        recv.await.expect("Actor task has been killed")
+        self.sender
+            .send(msg)
+            .await
+            .map_err(|_| HostError::Unavailable)?;
+        recv.await.map_err(|_| HostError::Unavailable)?
+    }
+
+    /// Looks up a running game without going through the game manager.
+    pub fn find_game(&self, game_code: u32) -> Option<GameActorHandle> {
+        self.registry.find(game_code)
+    }
+
+    /// Whether the server has started shutting down, after which games can't be hosted or joined.
+    pub fn is_shutting_down(&self) -> bool {
+        self.registry.is_closed()
+    }
+
+    /// Stops hosting and joining games, then has every running game tell its players and save its progress.
+    ///
+    /// Returns once every game has been saved. Games are saved concurrently, so the caller should bound this with
+    /// its own deadline.
+    pub async fn shutdown(&self) {
+        let games = self.registry.close();
+        tracing::info!("Saving {} running games", games.len());
+        join_all(games.iter().map(GameActorHandle::shutdown)).await;
     }
 }
 
+/// Runs the game actor, handling messages and expiring slides once their timers run out.
 async fn run_game_actor(mut actor: GameActor) {
-    while let Some(msg) = actor.receiver.recv().await {
-        actor.handle_message(msg).await;
+    let mut leaderboard_interval = tokio::time::interval(LEADERBOARD_INTERVAL);
+    leaderboard_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut lobby_interval = tokio::time::interval(LOBBY_INTERVAL);
+    lobby_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        let deadline = actor.session.next_deadline();
+        tokio::select! {
+            msg = actor.receiver.recv() => match msg {
+                Some(msg) => {
+                    let shutdown = matches!(msg, GameMessage::Shutdown { .. });
+                    actor.handle_message(msg).await;
+                    if shutdown {
+                        break;
+                    }
+                }
+                None => break,
+            },
+            _ = sleep_until(deadline) => {
+                actor.session.expire_slides(Instant::now());
+            }
+            _ = leaderboard_interval.tick() => {
+                actor.session.update_leaderboard();
+            }
+            _ = lobby_interval.tick() => {
+                actor.session.announce_players();
+            }
+        }
+        if !actor.session.is_active() {
+            actor.finish().await;
+            break;
+        }
+    }
+}
+
+/// Sleeps until the deadline, or forever if there is none.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
     }
 }
 
 struct GameManagerActor {
     receiver: mpsc::Receiver<LobbyMessage>,
-    games: HashMap<u32, GameActorHandle>,
-    db: Database,
+    registry: GameRegistry,
+    repository: Arc<dyn GameRepository>,
+    limits: LimitsConfig,
 }
 
 impl GameManagerActor {
-    fn new(receiver: mpsc::Receiver<LobbyMessage>, db: Database) -> Self {
+    fn new(
+        receiver: mpsc::Receiver<LobbyMessage>,
+        registry: GameRegistry,
+        repository: Arc<dyn GameRepository>,
+        limits: LimitsConfig,
+    ) -> Self {
         Self {
             receiver,
-            games: HashMap::new(),
-            db,
+            registry,
+            repository,
+            limits,
         }
     }
 
+    /// Loads a stored session from the database and registers a new game actor for it.
+    async fn host(&mut self, session_id: String, settings: GameSettings) -> Result<u32, HostError> {
+        if self.registry.is_closed() {
+            return Err(HostError::ShuttingDown);
+        }
+        if self.registry.is_hosted(&session_id) {
+            return Err(HostError::AlreadyHosted);
+        }
+
+        let stored_session = self
+            .repository
+            .load_session(&session_id)
+            .await
+            .map_err(HostError::Storage)?
+            .ok_or(HostError::NotFound)?;
+        let session = GameSession::from_stored(stored_session, settings);
+        session
+            .get_template()
+            .validate()
+            .map_err(HostError::InvalidTemplate)?;
+
+        let code = session.get_code();
+        if self.registry.get(code).is_some() {
+            return Err(HostError::DuplicateCode(code));
+        }
+        tracing::info!(session_id, "hosting game");
+        let handle = GameActorHandle::new(
+            session,
+            self.repository.clone(),
+            self.registry.clone(),
+            self.limits.game_mailbox,
+        );
+        self.registry.insert(code, handle)?;
+        Ok(code)
+    }
+
     async fn handle_message(&mut self, msg: LobbyMessage) {
         match msg {
-            LobbyMessage::Host { session_id } => {
-                let stored_session = self
-                    .db
-                    .collection::<StoredGameSession>("gameSessions")
-                    .find_one(doc! {"_id": session_id}, None)
-                    .await
-                    .unwrap()
-                    .unwrap();
-                let session = GameSession::from_stored(stored_session);
-
-                let code = session.get_code();
-                let handle = GameActorHandle::new(session);
-                self.games.insert(code, handle);
-            }
-            LobbyMessage::Join {
-                player,
-                game_code,
+            LobbyMessage::Host {
+                session_id,
+                settings,
                 respond_to,
             } => {
-                let game = self.games.get_mut(&game_code);
-                match game {
-                    Some(game) => {
-                        game.join_game(player).await;
-                        let _ = respond_to.send(Some(game.clone()));
-                    }
-                    None => {
-                        let _ = respond_to.send(None);
-                    }
-                }
+                let _ = respond_to.send(self.host(session_id, settings).await);
             }
         }
     }
@@ -140,31 +362,43 @@ async fn run_game_manager_actor(mut actor: GameManagerActor) {
 #[derive(Clone)]
 pub struct GameActorHandle {
     sender: mpsc::Sender<GameMessage>,
+    session_id: String,
 }
 
 impl GameActorHandle {
-    pub fn new(session: GameSession) -> Self {
-        let (sender, receiver) = mpsc::channel(8);
-        let actor = GameActor::new(receiver, session);
+    fn new(
+        session: GameSession,
+        repository: Arc<dyn GameRepository>,
+        registry: GameRegistry,
+        mailbox: usize,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(mailbox);
+        let session_id = session.get_id().to_string();
+        let actor = GameActor::new(receiver, session, repository, registry);
         tokio::spawn(run_game_actor(actor));
 
-        Self { sender }
+        Self { sender, session_id }
+    }
+
+    /// Sends a message to the game actor. Messages to a game that has already ended are dropped.
+    async fn send(&self, msg: GameMessage) {
+        let _ = self.sender.send(msg).await;
     }
 
     pub async fn start_game(&self, player_id: String) {
         let msg = GameMessage::Start { player_id };
-        self.sender
-            .send(msg)
-            .await
-            .expect("Actor task has been killed");
+        self.send(msg).await;
     }
 
-    pub async fn join_game(&self, player: Player) {
-        let msg = GameMessage::Join { player };
-        self.sender
-            .send(msg)
-            .await
-            .expect("Actor task has been killed");
+    /// Joins the player to the game, returning whether they were let in.
+    pub async fn join_game(&self, player: Player) -> bool {
+        let (send, recv) = oneshot::channel();
+        let msg = GameMessage::Join {
+            player,
+            respond_to: send,
+        };
+        self.send(msg).await;
+        recv.await.unwrap_or(false)
     }
 
     pub async fn submit_answer(
@@ -180,54 +414,107 @@ impl GameActorHandle {
             answer_time,
             slide_index,
         };
-        self.sender
-            .send(msg)
-            .await
-            .expect("Actor task has been killed");
+        self.send(msg).await;
     }
 
-    pub async fn leave(&self, player_id: String) {
-        let msg = GameMessage::Leave { player_id };
-        self.sender
-            .send(msg)
-            .await
-            .expect("Actor task hs been killed");
+    pub async fn next_slide(&self, player_id: String) {
+        let msg = GameMessage::Next { player_id };
+        self.send(msg).await;
+    }
+
+    pub async fn close_answers(&self, player_id: String) {
+        let msg = GameMessage::Close { player_id };
+        self.send(msg).await;
+    }
+
+    /// Marks the player as having left, unless the connection they left from has already been replaced.
+    pub async fn leave(&self, player_id: String, connection_id: ConnectionId) {
+        let msg = GameMessage::Leave {
+            player_id,
+            connection_id,
+        };
+        self.send(msg).await;
     }
 
     pub async fn kick(&self, kick_player_id: String, player_id: String) {
         let msg = GameMessage::Kick { kick_player_id, player_id };
-        self.sender
-            .send(msg)
-            .await
-            .expect("Actor task has been killed");
+        self.send(msg).await;
     }
 
     pub async fn end(&self, player_id: String) {
         let msg = GameMessage::End { player_id };
-        self.sender
-            .send(msg)
-            .await
-            .expect("Actor task has been killed");
+        self.send(msg).await;
+    }
+
+    /// Has the game save itself and stop, returning once it's done. Returns right away if the game already stopped.
+    async fn shutdown(&self) {
+        let (send, recv) = oneshot::channel();
+        let msg = GameMessage::Shutdown { respond_to: send };
+        self.send(msg).await;
+        let _ = recv.await;
     }
 }
 
 struct GameActor {
     receiver: mpsc::Receiver<GameMessage>,
     session: GameSession,
+    repository: Arc<dyn GameRepository>,
+    registry: GameRegistry,
 }
 
 impl GameActor {
-    fn new(receiver: mpsc::Receiver<GameMessage>, session: GameSession) -> Self {
-        Self { receiver, session }
+    fn new(
+        receiver: mpsc::Receiver<GameMessage>,
+        session: GameSession,
+        repository: Arc<dyn GameRepository>,
+        registry: GameRegistry,
+    ) -> Self {
+        Self {
+            receiver,
+            session,
+            repository,
+            registry,
+        }
+    }
+
+    /// Saves the game's players and their answers so far to its stored session.
+    async fn save_results(&self) {
+        let (players, player_answers) = self.session.to_stored_results();
+        if let Err(err) = self
+            .repository
+            .save_results(self.session.get_id(), &players, &player_answers)
+            .await
+        {
+            tracing::error!("Failed to save game results: {:#}", err);
+        }
+    }
+
+    /// Saves the ended game's players and answers to its stored session and removes it from the registry.
+    async fn finish(&mut self) {
+        self.save_results().await;
+        let session_id = self.session.get_id();
+        if let Err(err) = self.repository.mark_inactive(session_id).await {
+            tracing::error!("Failed to mark game session inactive: {:#}", err);
+        }
+
+        self.registry
+            .remove(self.session.get_code(), self.session.get_id());
+    }
+
+    /// Tells the players the server is going down and saves the game as it is. The stored session stays active, since
+    /// the game never finished.
+    async fn shutdown(&mut self) {
+        self.session.shutdown();
+        self.save_results().await;
     }
 
     async fn handle_message(&mut self, msg: GameMessage) {
         match msg {
-            GameMessage::Join { player } => {
-                self.session.join(player).await;
+            GameMessage::Join { player, respond_to } => {
+                let _ = respond_to.send(self.session.join(player));
             }
             GameMessage::Start { player_id } => {
-                self.session.start(player_id).await;
+                self.session.start(player_id);
             }
             GameMessage::Answer {
                 player_id,
@@ -236,18 +523,214 @@ impl GameActor {
                 slide_index,
             } => {
                 self.session
-                    .answer(player_id, answer, answer_time, slide_index)
-                    .await;
+                    .answer(player_id, answer, answer_time, slide_index);
+            }
+            GameMessage::Next { player_id } => {
+                self.session.next_slide(player_id);
             }
-            GameMessage::Leave { player_id } => {
-                self.session.leave(player_id).await;
+            GameMessage::Close { player_id } => {
+                self.session.close_answers(player_id);
+            }
+            GameMessage::Leave {
+                player_id,
+                connection_id,
+            } => {
+                self.session.leave(player_id, connection_id);
             }
             GameMessage::Kick { kick_player_id, player_id } => {
-                self.session.kick(kick_player_id, player_id).await;
+                self.session.kick(kick_player_id, player_id);
             }
             GameMessage::End { player_id } => {
-                self.session.host_end(player_id).await;
+                self.session.host_end(player_id);
+            }
+            // The actor stops right after
+            GameMessage::Shutdown { respond_to } => {
+                self.shutdown().await;
+                let _ = respond_to.send(());
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{PlayerMessage, StoredGameSession};
+    use crate::storage::InMemoryRepository;
+
+    /// Registers a game whose mailbox is read by the returned receiver instead of a game actor.
+    fn register_game(
+        registry: &GameRegistry,
+        game_code: u32,
+        capacity: usize,
+    ) -> mpsc::Receiver<GameMessage> {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let game = GameActorHandle {
+            sender,
+            session_id: format!("session{game_code}"),
+        };
+        registry.insert(game_code, game).unwrap();
+        receiver
+    }
+
+    fn player(index: usize) -> Player {
+        Player::new(format!("player{index}"), format!("player{index}"), None)
+    }
+
+    #[test]
+    fn registry_insert_remove() {
+        let registry = GameRegistry::default();
+        let _receiver = register_game(&registry, 12345, 1);
+        assert!(registry.is_hosted("session12345"));
+        assert!(matches!(
+            registry.insert(
+                12345,
+                GameActorHandle {
+                    sender: mpsc::channel(1).0,
+                    session_id: "other".to_string(),
+                }
+            ),
+            Err(HostError::DuplicateCode(12345))
+        ));
+
+        // Only the session that owns the code can remove it
+        registry.remove(12345, "other");
+        assert!(registry.get(12345).is_some());
+        registry.remove(12345, "session12345");
+        assert!(registry.get(12345).is_none());
+        assert!(!registry.is_hosted("session12345"));
+    }
+
+    #[test]
+    fn registry_find_stopped_game() {
+        let registry = GameRegistry::default();
+        drop(register_game(&registry, 12345, 1));
+        assert!(registry.find(12345).is_none());
+        assert!(registry.get(12345).is_none());
+        assert!(registry.find(54321).is_none());
+    }
+
+    #[tokio::test]
+    async fn shutdown_saves_games() {
+        let repository = Arc::new(InMemoryRepository::default());
+        let stored_session = StoredGameSession::fixture();
+        repository.insert_session(stored_session.clone());
+        let session = GameSession::from_stored(stored_session, GameSettings::default());
+
+        let registry = GameRegistry::default();
+        let game = GameActorHandle::new(session, repository.clone(), registry.clone(), 8);
+        registry.insert(12345, game.clone()).unwrap();
+        let (tx, mut rx) = mpsc::channel(32);
+        assert!(
+            game.join_game(Player::new(
+                "player1".to_string(),
+                "player1".to_string(),
+                Some(tx)
+            ))
+            .await
+        );
+
+        let game_manager = GameManagerActorHandle {
+            sender: mpsc::channel(1).0,
+            registry: registry.clone(),
+        };
+        game_manager.shutdown().await;
+        assert!(game_manager.is_shutting_down());
+        assert!(game_manager.find_game(12345).is_none());
+        assert!(matches!(
+            registry.insert(12345, game.clone()),
+            Err(HostError::ShuttingDown)
+        ));
+
+        // The player is told before the game drops their connection
+        let mut last_message = None;
+        while let Some(payload) = rx.recv().await {
+            last_message = Some(serde_json::from_str(payload.as_str()).unwrap());
+        }
+        assert_eq!(Some(PlayerMessage::ServerShutdown), last_message);
+
+        // The players are saved, but the game can still be picked up again
+        let stored = repository.load_session("session").await.unwrap().unwrap();
+        let stored = serde_json::to_value(stored).unwrap();
+        assert_eq!(serde_json::json!(true), stored["active"]);
+        assert_eq!(
+            serde_json::json!("player1"),
+            stored["players"][0]["player_id"]
+        );
+    }
+
+    #[tokio::test]
+    async fn host_game_without_game_manager() {
+        let mut game_manager = GameManagerActorHandle {
+            sender: mpsc::channel(1).0,
+            registry: GameRegistry::default(),
+        };
+        assert!(matches!(
+            game_manager
+                .host_game("session".to_string(), GameSettings::default())
+                .await,
+            Err(HostError::Unavailable)
+        ));
+    }
+
+    /// Acknowledges every join sent to the game until the mailbox closes.
+    async fn accept_joins(mut receiver: mpsc::Receiver<GameMessage>) {
+        while let Some(msg) = receiver.recv().await {
+            if let GameMessage::Join { respond_to, .. } = msg {
+                let _ = respond_to.send(true);
+            }
+        }
+    }
+
+    /// Runs on a paused clock, so latencies only count time the joins spent waiting on timers, and a join stuck
+    /// behind the saturated game idles the runtime until the timeout fires instead of racing a wall clock.
+    #[tokio::test(start_paused = true)]
+    async fn join_latency_with_saturated_game() {
+        const JOINS: usize = 1000;
+        let registry = GameRegistry::default();
+
+        // A game that never reads its mailbox, with a join already waiting on it
+        let mut saturated = register_game(&registry, 11111, 1);
+        let saturated_game = registry.find(11111).unwrap();
+        let (respond_to, _) = oneshot::channel();
+        saturated_game
+            .send(GameMessage::Join {
+                player: player(0),
+                respond_to,
+            })
+            .await;
+        let blocked_join = tokio::spawn({
+            let saturated_game = saturated_game.clone();
+            async move { saturated_game.join_game(player(1)).await }
+        });
+
+        let healthy = register_game(&registry, 22222, 32);
+        tokio::spawn(accept_joins(healthy));
+
+        let mut joins = Vec::with_capacity(JOINS);
+        for index in 0..JOINS {
+            let registry = registry.clone();
+            joins.push(tokio::spawn(async move {
+                let start = tokio::time::Instant::now();
+                let game = registry.find(22222).unwrap();
+                assert!(game.join_game(player(index)).await);
+                start.elapsed()
+            }));
+        }
+        let mut slowest = Duration::ZERO;
+        for join in joins {
+            let latency = tokio::time::timeout(Duration::from_secs(5), join)
+                .await
+                .expect("join to a healthy game waited on the saturated one")
+                .unwrap();
+            slowest = slowest.max(latency);
+        }
+        assert_eq!(Duration::ZERO, slowest, "slowest join took {slowest:?}");
+
+        // The saturated game is still stuck, only its own join waits on it
+        assert!(!blocked_join.is_finished());
+        let _ = saturated.recv().await;
+        tokio::spawn(accept_joins(saturated));
+        assert!(blocked_join.await.unwrap());
+    }
+}
@@ -0,0 +1,537 @@
+//! Server configuration, merged from a TOML file, environment variables and command line flags.
+//!
+//! Every setting has a default, so the file is optional. Flags take precedence over environment variables, which take
+//! precedence over the file.
+
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+use std::{fmt, fs, io, net::SocketAddr, path::PathBuf, time::Duration};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub sessions: SessionsConfig,
+    pub storage: StorageConfig,
+    pub timeouts: TimeoutsConfig,
+    pub limits: LimitsConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address the HTTP and websocket server listens on.
+    pub bind: SocketAddr,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: SocketAddr::from(([127, 0, 0, 1], 3000)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionBackend {
+    Redis,
+    /// Only guests can join then, as nothing logs players in.
+    Memory,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionsConfig {
+    pub store: SessionBackend,
+    pub redis_url: String,
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        SessionsConfig {
+            store: SessionBackend::Redis,
+            redis_url: "redis://127.0.0.1/".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Mongo,
+    /// Optionally loaded from and saved to a JSON file.
+    Memory,
+    Sqlite,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub store: StorageBackend,
+    pub mongo_url: String,
+    pub database: String,
+    /// The JSON file of the memory store or the SQLite database, which defaults to `gudritis.db`.
+    pub file: Option<PathBuf>,
+    /// JSON file in the memory store's format whose game sessions and users are added to the SQLite database on
+    /// startup.
+    pub import_file: Option<PathBuf>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            store: StorageBackend::Mongo,
+            mongo_url: "mongodb://localhost:27017".to_string(),
+            database: "Gudritis".to_string(),
+            file: None,
+            import_file: None,
+        }
+    }
+}
+
+impl StorageConfig {
+    pub fn sqlite_file(&self) -> PathBuf {
+        self.file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("gudritis.db"))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutsConfig {
+    /// How often clients are pinged.
+    pub heartbeat_interval_secs: u64,
+    /// How long a client can stay silent before being marked as disconnected.
+    pub idle_timeout_secs: u64,
+    /// How long a single Redis command may take before giving up on it.
+    pub redis_command_secs: u64,
+    /// How long running games get to save themselves once the server is told to stop, before it exits regardless.
+    pub shutdown_secs: u64,
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        TimeoutsConfig {
+            heartbeat_interval_secs: 15,
+            idle_timeout_secs: 45,
+            redis_command_secs: 2,
+            shutdown_secs: 10,
+        }
+    }
+}
+
+impl TimeoutsConfig {
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_interval_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_secs)
+    }
+
+    pub fn redis_command(&self) -> Duration {
+        Duration::from_secs(self.redis_command_secs)
+    }
+
+    pub fn shutdown(&self) -> Duration {
+        Duration::from_secs(self.shutdown_secs)
+    }
+}
+
+/// Capacities of the channels between the websockets and the actors.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// How many messages can queue up for a client before it's considered too slow and disconnected.
+    pub client_buffer: usize,
+    /// How many commands can queue up for a single game.
+    pub game_mailbox: usize,
+    /// How many host requests can queue up for the game manager.
+    pub manager_mailbox: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            client_buffer: 32,
+            game_mailbox: 8,
+            manager_mailbox: 32,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// The most verbose level that gets logged: `error`, `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "info".to_string(),
+        }
+    }
+}
+
+impl LoggingConfig {
+    pub fn level(&self) -> Option<tracing::Level> {
+        self.level.parse().ok()
+    }
+}
+
+/// Command line flags, each of which can also be set through the environment variable next to it. The variables are
+/// prefixed so they can't be picked up from settings meant for other programs.
+#[derive(Debug, Default, Parser)]
+#[command(about = "Gudritis game server")]
+pub struct Cli {
+    /// TOML file to read the configuration from.
+    #[arg(long, env = "GUDRITIS_CONFIG")]
+    pub config: Option<PathBuf>,
+    #[arg(long, env = "GUDRITIS_BIND")]
+    pub bind: Option<SocketAddr>,
+    #[arg(long, env = "GUDRITIS_SESSION_STORE")]
+    pub session_store: Option<SessionBackend>,
+    #[arg(long, env = "GUDRITIS_REDIS_URL")]
+    pub redis_url: Option<String>,
+    #[arg(long, env = "GUDRITIS_GAME_STORE")]
+    pub game_store: Option<StorageBackend>,
+    #[arg(long, env = "GUDRITIS_MONGO_URL")]
+    pub mongo_url: Option<String>,
+    #[arg(long, env = "GUDRITIS_MONGO_DATABASE")]
+    pub database: Option<String>,
+    #[arg(long, env = "GUDRITIS_GAME_STORE_FILE")]
+    pub game_store_file: Option<PathBuf>,
+    #[arg(long, env = "GUDRITIS_IMPORT_FILE")]
+    pub import_file: Option<PathBuf>,
+    #[arg(long, env = "GUDRITIS_HEARTBEAT_INTERVAL_SECS")]
+    pub heartbeat_interval_secs: Option<u64>,
+    #[arg(long, env = "GUDRITIS_IDLE_TIMEOUT_SECS")]
+    pub idle_timeout_secs: Option<u64>,
+    #[arg(long, env = "GUDRITIS_REDIS_COMMAND_SECS")]
+    pub redis_command_secs: Option<u64>,
+    #[arg(long, env = "GUDRITIS_SHUTDOWN_SECS")]
+    pub shutdown_secs: Option<u64>,
+    #[arg(long, env = "GUDRITIS_CLIENT_BUFFER")]
+    pub client_buffer: Option<usize>,
+    #[arg(long, env = "GUDRITIS_GAME_MAILBOX")]
+    pub game_mailbox: Option<usize>,
+    #[arg(long, env = "GUDRITIS_MANAGER_MAILBOX")]
+    pub manager_mailbox: Option<usize>,
+    #[arg(long, env = "GUDRITIS_LOG_LEVEL")]
+    pub log_level: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    /// Every setting that failed validation, so they can all be fixed in one go.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => {
+                write!(f, "could not read config file {}: {err}", path.display())
+            }
+            ConfigError::Parse(path, err) => {
+                write!(f, "invalid config file {}: {err}", path.display())
+            }
+            ConfigError::Invalid(problems) => {
+                write!(f, "invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {problem}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads the configuration from the command line, the environment and the config file they point to.
+    ///
+    /// Exits the process with a usage message if the flags themselves can't be parsed.
+    pub fn load() -> Result<Config, ConfigError> {
+        Config::from_cli(Cli::parse())
+    }
+
+    pub fn from_cli(cli: Cli) -> Result<Config, ConfigError> {
+        let mut config = match &cli.config {
+            Some(path) => {
+                let contents =
+                    fs::read_to_string(path).map_err(|err| ConfigError::Read(path.clone(), err))?;
+                Config::from_toml(&contents).map_err(|err| ConfigError::Parse(path.clone(), err))?
+            }
+            None => Config::default(),
+        };
+        config.apply(cli);
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_toml(contents: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Overrides the settings that were given on the command line or in the environment.
+    fn apply(&mut self, cli: Cli) {
+        if let Some(bind) = cli.bind {
+            self.server.bind = bind;
+        }
+        if let Some(store) = cli.session_store {
+            self.sessions.store = store;
+        }
+        if let Some(redis_url) = cli.redis_url {
+            self.sessions.redis_url = redis_url;
+        }
+        if let Some(store) = cli.game_store {
+            self.storage.store = store;
+        }
+        if let Some(mongo_url) = cli.mongo_url {
+            self.storage.mongo_url = mongo_url;
+        }
+        if let Some(database) = cli.database {
+            self.storage.database = database;
+        }
+        if let Some(file) = cli.game_store_file {
+            self.storage.file = Some(file);
+        }
+        if let Some(file) = cli.import_file {
+            self.storage.import_file = Some(file);
+        }
+        if let Some(secs) = cli.heartbeat_interval_secs {
+            self.timeouts.heartbeat_interval_secs = secs;
+        }
+        if let Some(secs) = cli.idle_timeout_secs {
+            self.timeouts.idle_timeout_secs = secs;
+        }
+        if let Some(secs) = cli.redis_command_secs {
+            self.timeouts.redis_command_secs = secs;
+        }
+        if let Some(secs) = cli.shutdown_secs {
+            self.timeouts.shutdown_secs = secs;
+        }
+        if let Some(capacity) = cli.client_buffer {
+            self.limits.client_buffer = capacity;
+        }
+        if let Some(capacity) = cli.game_mailbox {
+            self.limits.game_mailbox = capacity;
+        }
+        if let Some(capacity) = cli.manager_mailbox {
+            self.limits.manager_mailbox = capacity;
+        }
+        if let Some(level) = cli.log_level {
+            self.logging.level = level;
+        }
+    }
+
+    /// Checks the settings that would otherwise only fail once the server is running, or not at all.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.sessions.store == SessionBackend::Redis
+            && redis::parse_redis_url(&self.sessions.redis_url).is_none()
+        {
+            problems.push(format!(
+                "sessions.redis_url: `{}` is not a redis:// URL",
+                self.sessions.redis_url
+            ));
+        }
+        if self.storage.store == StorageBackend::Mongo {
+            if !self.storage.mongo_url.starts_with("mongodb://")
+                && !self.storage.mongo_url.starts_with("mongodb+srv://")
+            {
+                problems.push(format!(
+                    "storage.mongo_url: `{}` is not a mongodb:// URL",
+                    self.storage.mongo_url
+                ));
+            }
+            if self.storage.database.is_empty() {
+                problems.push("storage.database: must not be empty".to_string());
+            }
+        }
+        if self.storage.import_file.is_some() && self.storage.store != StorageBackend::Sqlite {
+            problems
+                .push("storage.import_file: only the sqlite store imports sessions".to_string());
+        }
+
+        let timeouts = &self.timeouts;
+        if timeouts.heartbeat_interval_secs == 0 {
+            problems.push("timeouts.heartbeat_interval_secs: must be at least 1".to_string());
+        }
+        // Clients only have to answer pings, so they'd be dropped between two of them otherwise
+        if timeouts.idle_timeout_secs <= timeouts.heartbeat_interval_secs {
+            problems.push(format!(
+                "timeouts.idle_timeout_secs: must be longer than the heartbeat interval of {}s",
+                timeouts.heartbeat_interval_secs
+            ));
+        }
+        if timeouts.redis_command_secs == 0 {
+            problems.push("timeouts.redis_command_secs: must be at least 1".to_string());
+        }
+        if timeouts.shutdown_secs == 0 {
+            problems.push("timeouts.shutdown_secs: must be at least 1".to_string());
+        }
+
+        let limits = [
+            ("client_buffer", self.limits.client_buffer),
+            ("game_mailbox", self.limits.game_mailbox),
+            ("manager_mailbox", self.limits.manager_mailbox),
+        ];
+        for (name, capacity) in limits {
+            if capacity == 0 {
+                problems.push(format!("limits.{name}: must be at least 1"));
+            }
+        }
+
+        if self.logging.level().is_none() {
+            problems.push(format!(
+                "logging.level: `{}` is not one of error, warn, info, debug or trace",
+                self.logging.level
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(config: &Config) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => vec![],
+            Err(ConfigError::Invalid(problems)) => problems,
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn config_defaults() {
+        let config = Config::default();
+        assert!(problems(&config).is_empty());
+        assert_eq!(config.server.bind.to_string(), "127.0.0.1:3000");
+        assert_eq!(config.sessions.store, SessionBackend::Redis);
+        assert_eq!(config.storage.store, StorageBackend::Mongo);
+        assert_eq!(config.storage.database, "Gudritis");
+        assert_eq!(config.storage.sqlite_file(), PathBuf::from("gudritis.db"));
+        assert_eq!(
+            config.timeouts.heartbeat_interval(),
+            Duration::from_secs(15)
+        );
+        assert_eq!(config.timeouts.shutdown(), Duration::from_secs(10));
+        assert_eq!(config.limits.client_buffer, 32);
+        assert_eq!(config.logging.level(), Some(tracing::Level::INFO));
+    }
+
+    #[test]
+    fn config_from_toml() {
+        let config = Config::from_toml(
+            r#"
+            [server]
+            bind = "0.0.0.0:8080"
+
+            [storage]
+            store = "sqlite"
+            file = "games.db"
+
+            [limits]
+            game_mailbox = 64
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.server.bind.to_string(), "0.0.0.0:8080");
+        assert_eq!(config.storage.store, StorageBackend::Sqlite);
+        assert_eq!(config.storage.sqlite_file(), PathBuf::from("games.db"));
+        assert_eq!(config.limits.game_mailbox, 64);
+        // Settings missing from the file keep their defaults
+        assert_eq!(config.limits.client_buffer, 32);
+        assert_eq!(config.sessions.store, SessionBackend::Redis);
+
+        // Typos are caught instead of silently falling back to the default
+        let err = Config::from_toml("[timeouts]\nidle_timeout = 30").unwrap_err();
+        assert!(err.to_string().contains("idle_timeout"));
+        assert!(Config::from_toml("[storage]\nstore = \"postgres\"").is_err());
+    }
+
+    #[test]
+    fn config_overrides() {
+        let mut config = Config::from_toml(
+            r#"
+            [sessions]
+            store = "memory"
+
+            [timeouts]
+            heartbeat_interval_secs = 10
+            idle_timeout_secs = 30
+            "#,
+        )
+        .unwrap();
+        let cli = Cli::try_parse_from([
+            "gudritis",
+            "--session-store",
+            "redis",
+            "--idle-timeout-secs",
+            "60",
+            "--bind",
+            "127.0.0.1:4000",
+            "--redis-command-secs",
+            "5",
+            "--game-mailbox",
+            "64",
+        ])
+        .unwrap();
+        config.apply(cli);
+        assert_eq!(config.sessions.store, SessionBackend::Redis);
+        assert_eq!(config.timeouts.idle_timeout_secs, 60);
+        assert_eq!(config.timeouts.heartbeat_interval_secs, 10);
+        assert_eq!(config.server.bind.to_string(), "127.0.0.1:4000");
+        assert_eq!(config.timeouts.redis_command_secs, 5);
+        assert_eq!(config.limits.game_mailbox, 64);
+        assert_eq!(config.limits.client_buffer, 32);
+
+        assert!(Cli::try_parse_from(["gudritis", "--game-store", "postgres"]).is_err());
+    }
+
+    #[test]
+    fn config_validation() {
+        let mut config = Config::default();
+        config.sessions.redis_url = "localhost:6379".to_string();
+        config.storage.database = String::new();
+        config.storage.import_file = Some(PathBuf::from("games.json"));
+        config.timeouts.idle_timeout_secs = 15;
+        config.limits.game_mailbox = 0;
+        config.logging.level = "verbose".to_string();
+
+        let problems = problems(&config);
+        assert_eq!(problems.len(), 6);
+        assert!(problems[0].starts_with("sessions.redis_url"));
+        assert!(problems[1].starts_with("storage.database"));
+        assert!(problems[2].starts_with("storage.import_file"));
+        assert!(problems[3].starts_with("timeouts.idle_timeout_secs"));
+        assert!(problems[4].starts_with("limits.game_mailbox"));
+        assert!(problems[5].starts_with("logging.level"));
+
+        // URLs of backends that aren't used don't matter
+        config.sessions.store = SessionBackend::Memory;
+        config.storage.store = StorageBackend::Sqlite;
+        config.timeouts.idle_timeout_secs = 45;
+        config.limits.game_mailbox = 8;
+        config.logging.level = "debug".to_string();
+        assert!(self::problems(&config).is_empty());
+    }
+}
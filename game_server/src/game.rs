@@ -1,4 +1,12 @@
-use std::{collections::HashMap, time::Instant, vec};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    vec,
+};
 
 use axum::extract::ws::Message;
 
@@ -6,17 +14,63 @@ use rand::seq::SliceRandom;
 use rand::thread_rng;
 
 use mongodb::bson::DateTime;
+
+use crate::metrics;
+use crate::scoring::{ScoredAnswer, Scoring, ScoringPolicy};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::{error::TrySendError, Sender};
+
+type Tx = Sender<Payload>;
+
+/// A message serialized once and shared by every connection it's sent to, so fanning it out only clones a pointer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Payload(Arc<str>);
+
+impl Payload {
+    fn new(message: &PlayerMessage) -> Self {
+        Payload(serde_json::to_string(message).unwrap().into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Payload {
+    fn from(text: &str) -> Self {
+        Payload(text.into())
+    }
+}
+
+impl From<Payload> for Message {
+    fn from(payload: Payload) -> Self {
+        Message::Text(payload.0.to_string())
+    }
+}
+
+/// Identifies one of a player's connections, so a connection that has since been replaced can't act for the new one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnectionId(u64);
 
-type Tx = Sender<Message>;
+impl ConnectionId {
+    fn next() -> Self {
+        static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(0);
+        ConnectionId(NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Player {
     player_id: String,
     nickname: String,
     connection: Option<Tx>,
+    /// The latest connection the player was given. Kept when the game drops the connection, so its leave still counts.
+    connection_id: Option<ConnectionId>,
+    /// Whether the player is logged in, as opposed to playing as a guest.
+    registered: bool,
+    /// Whether the connection was dropped for falling behind, so the messages the player misses count as dropped.
+    fell_behind: bool,
 }
 
 impl Player {
@@ -24,27 +78,72 @@ impl Player {
         Player {
             player_id,
             nickname,
+            connection_id: tx.as_ref().map(|_| ConnectionId::next()),
             connection: tx,
+            registered: true,
+            fell_behind: false,
+        }
+    }
+
+    /// Creates a player without an account.
+    pub fn guest(player_id: String, nickname: String, tx: Option<Tx>) -> Self {
+        Player {
+            registered: false,
+            ..Player::new(player_id, nickname, tx)
         }
     }
 
-    /// Replaces the players `Sender` with a new one
-    pub fn new_connection(&mut self, tx: Sender<Message>) {
+    /// Replaces the players `Sender` with a new one, returning the id the connection leaves the game with.
+    pub fn new_connection(&mut self, tx: Tx) -> ConnectionId {
+        let connection_id = ConnectionId::next();
         self.connection = Some(tx);
+        self.connection_id = Some(connection_id);
+        self.fell_behind = false;
+        connection_id
     }
 
     /// Sends a simple text message to the player.
-    pub async fn send_text_message(&mut self, text_message: &str) {
-        if let Some(connection) = &self.connection {
-            let _ = connection.send(text_message.into()).await;
-        }
+    pub fn send_text_message(&mut self, text_message: &str) {
+        self.deliver(text_message.into());
     }
 
     /// Sends a `PlayerMessage` to the player by serializing it into text.
-    async fn send_message(&mut self, message: PlayerMessage) {
-        if let Some(tx) = &self.connection {
-            let serialized_message = serde_json::to_string(&message).unwrap();
-            let _ = tx.send(Message::Text(serialized_message)).await;
+    fn send_message(&mut self, message: PlayerMessage) {
+        if self.connection.is_some() {
+            self.deliver(Payload::new(&message));
+        } else {
+            self.skip_message();
+        }
+    }
+
+    /// Queues a message on the player's connection without waiting.
+    ///
+    /// A player whose queue is full has stopped reading, so the connection is dropped instead of
+    /// holding up the game.
+    fn deliver(&mut self, message: Payload) {
+        let Some(tx) = &self.connection else {
+            self.skip_message();
+            return;
+        };
+        match tx.try_send(message) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                tracing::warn!("Disconnecting slow client {}", self.player_id);
+                metrics::record_slow_consumer();
+                metrics::record_dropped_message();
+                self.connection = None;
+                self.fell_behind = true;
+            }
+            Err(TrySendError::Closed(_)) => {
+                self.connection = None;
+            }
+        }
+    }
+
+    /// Counts a message that won't reach the player because they were dropped for falling behind.
+    fn skip_message(&self) {
+        if self.fell_behind {
+            metrics::record_dropped_message();
         }
     }
 
@@ -68,6 +167,14 @@ struct SlideStack {
 struct CurrentSlide {
     starting_time: Instant,
     index: usize,
+    answered: bool,
+}
+
+impl CurrentSlide {
+    /// Returns the moment the slide's time runs out.
+    fn deadline(&self, duration: u8) -> Instant {
+        self.starting_time + Duration::from_secs(duration as u64)
+    }
 }
 
 impl SlideStack {
@@ -83,6 +190,15 @@ impl SlideStack {
         }
     }
 
+    /// Creates a new `SlideStack` of a given size that goes through the slides in template order.
+    fn ordered(size: usize) -> Self {
+        Self {
+            slide_stack: (0..size).rev().collect(),
+            current_slide: None,
+            answered_slides: Vec::with_capacity(size),
+        }
+    }
+
     /// Progresses the slides by one returning the next one. Sets current_slide to the new slide.
     fn next_slide(&mut self) -> Option<usize> {
         let next = self.slide_stack.pop();
@@ -94,6 +210,7 @@ impl SlideStack {
             self.current_slide = Some(CurrentSlide {
                 starting_time: Instant::now(),
                 index,
+                answered: false,
             });
         }
         next
@@ -103,15 +220,109 @@ impl SlideStack {
     fn current_slide(&self) -> Option<CurrentSlide> {
         self.current_slide
     }
+
+    /// Marks the current slide as answered so it isn't answered again or timed out.
+    fn mark_answered(&mut self) {
+        if let Some(current_slide) = &mut self.current_slide {
+            current_slide.answered = true;
+        }
+    }
+
+    /// Replaces the current slide with one picked from outside the stack, as the host does in host-paced games.
+    fn show(&mut self, slide: CurrentSlide) {
+        if let Some(current_slide) = &self.current_slide {
+            self.answered_slides.push(current_slide.index);
+        }
+        self.slide_stack.retain(|index| *index != slide.index);
+        self.current_slide = Some(slide);
+    }
+
+    /// Whether every slide has been shown and there is no current slide left.
+    fn is_finished(&self) -> bool {
+        self.current_slide.is_none()
+            && self.slide_stack.is_empty()
+            && !self.answered_slides.is_empty()
+    }
+
+    /// Ends the stack without a current slide.
+    fn finish(&mut self) {
+        if let Some(current_slide) = self.current_slide.take() {
+            self.answered_slides.push(current_slide.index);
+        }
+        self.slide_stack.clear();
+    }
 }
 
 pub struct GameSession {
-    //stage: GameStage,
+    stage: GameStage,
+    id: String,
     code: u32,
-    active: bool,
     host: Player,
     players: HashMap<String, SessionPlayer>,
     template: GameTemplate,
+    settings: GameSettings,
+    scoring: Box<dyn ScoringPolicy>,
+    /// Rank and score of every player as of the last leaderboard update, used for the deltas.
+    last_standings: HashMap<String, (usize, i32)>,
+    /// Set when scores or players change, so unchanged leaderboards aren't resent.
+    standings_changed: bool,
+    /// Set when players join or are kicked, so everyone gets the new player list with the next lobby update.
+    lobby_changed: bool,
+    /// The slides everyone goes through together in host-paced games. `None` in self-paced games.
+    ///
+    /// The current slide is marked as answered once its answers are closed.
+    shared_slides: Option<SlideStack>,
+}
+
+/// Options the host picks when hosting a game session.
+#[derive(Debug, Clone, Default)]
+pub struct GameSettings {
+    /// Moves players to their next slide as soon as they answer instead of waiting for a `Next` command.
+    pub auto_advance: bool,
+    /// How answers are turned into points.
+    pub scoring: Scoring,
+    /// How submissions on multiple answer slides are graded.
+    pub grading: Grading,
+    /// Whether players go through the slides on their own or together with the host.
+    pub mode: GameMode,
+    /// Only lets logged in players join.
+    pub forbid_guests: bool,
+}
+
+/// How players move through the slides.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    /// Every player gets their own shuffled stack of slides and moves on at their own pace.
+    #[default]
+    SelfPaced,
+    /// The host moves everyone through the same slide in template order.
+    HostPaced,
+}
+
+/// How a submission is compared against a slide's correct answers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grading {
+    /// Only picking exactly the correct answers earns credit.
+    #[default]
+    Exact,
+    /// Each correct pick earns a share of the credit and each wrong pick takes one away.
+    Partial,
+}
+
+/// Reasons a submitted answer is rejected before grading.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnswerError {
+    /// Nothing was picked.
+    Empty,
+    /// The picked answer doesn't exist on the slide.
+    OutOfRange { index: usize },
+    /// The same answer was picked more than once.
+    Duplicate { index: usize },
+    /// Several answers were picked on a single answer slide.
+    MultiplePicks,
 }
 
 #[derive(Debug, PartialEq)]
@@ -126,15 +337,123 @@ pub struct SessionPlayer {
     status: PlayerStatus,
     answers: Vec<PlayerAnswer>,
     slide_stack: SlideStack,
+    score: i32,
+    /// Number of correct answers in a row, reset by a wrong answer or running out of time.
+    streak: u32,
 }
 
-impl GameSession {
-    pub fn from_stored(stored_session: StoredGameSession) -> Self {
-        let host = Player {
-            player_id: stored_session.host,
-            nickname: String::from("Host"),
-            connection: None,
+impl SessionPlayer {
+    fn new(player: Player, slide_count: usize) -> Self {
+        let status = if player.connection.is_some() {
+            PlayerStatus::Connected
+        } else {
+            PlayerStatus::Disconnected
         };
+        SessionPlayer {
+            player,
+            status,
+            answers: Vec::new(),
+            slide_stack: SlideStack::new(slide_count),
+            score: 0,
+            streak: 0,
+        }
+    }
+
+    /// Scores the answer with the given policy and records it. The points aren't added to the player's running score,
+    /// as host-paced games hold them back until the round is revealed.
+    ///
+    /// Returns the points the answer was worth.
+    fn record_answer(
+        &mut self,
+        mut answer: PlayerAnswer,
+        answered: bool,
+        credit: f64,
+        duration: u8,
+        scoring: &dyn ScoringPolicy,
+    ) -> i32 {
+        let points = scoring.score(&ScoredAnswer {
+            answered,
+            correct: answer.correct,
+            credit,
+            elapsed: answer.answer_submit_time - answer.slide_start_time,
+            duration: Duration::from_secs(duration as u64),
+            streak: self.streak,
+        });
+        if answer.correct {
+            self.streak += 1;
+        } else {
+            self.streak = 0;
+        }
+        answer.points = points;
+        self.answers.push(answer);
+        points
+    }
+
+    /// Sends a message to the player, marking them disconnected if they had to be dropped for falling behind.
+    fn send_message(&mut self, message: PlayerMessage) {
+        let was_connected = self.player.connection.is_some();
+        self.player.send_message(message);
+        self.check_connection(was_connected);
+    }
+
+    fn deliver(&mut self, message: Payload) {
+        let was_connected = self.player.connection.is_some();
+        self.player.deliver(message);
+        self.check_connection(was_connected);
+    }
+
+    fn check_connection(&mut self, was_connected: bool) {
+        if was_connected && self.player.connection.is_none() {
+            self.status = PlayerStatus::Disconnected;
+        }
+    }
+
+    /// Records an empty answer for a slide the player ran out of time on and lets them know.
+    fn time_out(
+        &mut self,
+        current_slide: CurrentSlide,
+        slide: &Slide,
+        scoring: &dyn ScoringPolicy,
+    ) {
+        self.score += self.record_answer(
+            PlayerAnswer {
+                player_id: self.player.player_id.clone(),
+                slide_index: current_slide.index,
+                slide_start_time: current_slide.starting_time,
+                answer: vec![],
+                answer_submit_time: current_slide.deadline(slide.duration),
+                correct: false,
+                points: 0,
+            },
+            false,
+            0.0,
+            slide.duration,
+            scoring,
+        );
+        self.slide_stack.mark_answered();
+        self.send_message(PlayerMessage::TimeUp {
+            slide_index: current_slide.index,
+            correct_answers: slide.correct_answer.clone(),
+        });
+    }
+
+    /// Moves the player to their next slide and sends it, or marks them as finished if the stack ran out.
+    fn advance(&mut self, template: &GameTemplate) {
+        self.slide_stack.next_slide();
+        if let Some(current_slide) = self.slide_stack.current_slide() {
+            self.send_message(PlayerMessage::Slide {
+                slide: template.get_player_slide(current_slide),
+            });
+        } else {
+            self.status = PlayerStatus::Finished;
+            self.send_message(PlayerMessage::Finish);
+        }
+    }
+}
+
+impl GameSession {
+    pub fn from_stored(stored_session: StoredGameSession, settings: GameSettings) -> Self {
+        let host = Player::new(stored_session.host, String::from("Host"), None);
 
         let stored_template = stored_session.template;
         let template = GameTemplate {
@@ -144,86 +463,202 @@ impl GameSession {
             author_id: stored_template.author_id,
         };
 
+        let shared_slides = match settings.mode {
+            GameMode::SelfPaced => None,
+            GameMode::HostPaced => Some(SlideStack::ordered(template.get_slide_count())),
+        };
+
         GameSession {
-            //stage: GameStage::Lobby,
+            stage: GameStage::Lobby,
+            id: stored_session._id,
             code: stored_session.code,
-            active: true,
             host,
             players: HashMap::new(),
             template,
+            scoring: settings.scoring.policy(),
+            settings,
+            last_standings: HashMap::new(),
+            standings_changed: false,
+            lobby_changed: false,
+            shared_slides,
         }
     }
 
-    pub async fn start(&mut self, player_id: String) {
+    pub fn start(&mut self, player_id: String) {
         if player_id != self.host.player_id {
             self.send_to(
                 PlayerMessage::Error {
                     err: "Not the host".to_string(),
                 },
                 &player_id,
-            )
-            .await;
+            );
+            return;
+        }
+        if !self.in_stage(&[GameStage::Lobby], &player_id) {
+            return;
+        }
+        self.set_stage(GameStage::Game);
+        if self.shared_slides.is_some() {
+            self.advance_round();
             return;
         }
         for session_player in self.players.values_mut() {
-            let slide_index = session_player.slide_stack.next_slide();
-            if let Some(slide_index) = slide_index {
-                session_player
-                    .player
-                    .send_message(PlayerMessage::Slide {
-                        slide: self.template.get_slide(slide_index),
-                    })
-                    .await;
-            } else {
-                todo!("add player stack end handling");
-            }
+            session_player.advance(&self.template);
         }
     }
 
-    pub async fn join(&mut self, mut player: Player) {
+    /// Adds the player to the game, or reconnects them if they've joined before. Returns whether they were let in.
+    ///
+    /// The player is sent a `Joined` acknowledgement with what they were assigned, followed by the game's state.
+    pub fn join(&mut self, mut player: Player) -> bool {
         let player_id = player.get_id().to_string();
         let nickname = player.get_nickname().to_string();
-        if player.player_id == self.host.player_id {
-            player.send_text_message("hostJoin").await;
+        if self.stage == GameStage::End {
+            player.send_message(PlayerMessage::Error {
+                err: "Game has ended".to_string(),
+            });
+            return false;
+        }
+        if !player.registered && self.settings.forbid_guests {
+            player.send_message(PlayerMessage::Error {
+                err: "Log in to join this game".to_string(),
+            });
+            return false;
+        }
+        let is_host = player_id == self.host.player_id;
+        // The host is part of the game from the start, so they've only been here before if they had a connection
+        let reconnected = if is_host {
+            self.host.connection_id.is_some()
+        } else {
+            self.players.contains_key(&player_id)
+        };
+        let mut resume = None;
+        let mut first_join = false;
+        if is_host {
+            player.send_text_message("hostJoin");
             self.host = player;
+            if reconnected {
+                resume = Some(self.host_snapshot());
+            }
         } else if let Some(session_player) = self.players.get_mut(&player.player_id) {
             session_player.player.connection = player.connection;
+            session_player.player.connection_id = player.connection_id;
+            session_player.player.fell_behind = false;
             session_player.player.nickname = player.nickname;
-            session_player.status = PlayerStatus::Connected;
+            session_player.status = if session_player.slide_stack.is_finished() {
+                PlayerStatus::Finished
+            } else {
+                PlayerStatus::Connected
+            };
+            resume = self.player_snapshot(&player_id);
         } else {
             self.players.insert(
                 player.get_id().to_string(),
-                SessionPlayer {
-                    player,
-                    status: PlayerStatus::Disconnected,
-                    answers: Vec::new(),
-                    slide_stack: SlideStack::new(self.template.get_slide_count()),
-                },
+                SessionPlayer::new(player, self.template.get_slide_count()),
             );
+            self.standings_changed = true;
+            self.lobby_changed = true;
+            first_join = true;
         }
-        let mut player_names: Vec<String> = vec![];
-        for session_player in self.players.values() {
-            player_names.push(session_player.player.nickname.clone());
-        }
-        self.send_to(PlayerMessage::Players { player_names }, &player_id)
-            .await;
-        self.emit(
-            PlayerMessage::PlayerJoin {
-                player_name: nickname.to_string(),
+        self.send_to(
+            PlayerMessage::Joined {
+                player_id: player_id.clone(),
+                nickname: nickname.clone(),
+                host: is_host,
+                stage: self.stage,
+                reconnected,
             },
-            player_id,
-        )
-        .await;
+            &player_id,
+        );
+        let player_names = self.player_names();
+        self.send_to(PlayerMessage::Players { player_names }, &player_id);
+        self.send_to(
+            PlayerMessage::StageChanged { stage: self.stage },
+            &player_id,
+        );
+        if first_join && self.stage == GameStage::Game {
+            self.catch_up(&player_id);
+        }
+        if let Some(resume) = resume {
+            self.send_to(resume, &player_id);
+            if player_id != self.host.player_id {
+                self.emit(
+                    PlayerMessage::Presence {
+                        player_id: player_id.clone(),
+                        nickname: nickname.clone(),
+                        connected: true,
+                    },
+                    player_id.clone(),
+                );
+            }
+        }
+        true
+    }
+
+    /// Sends everyone the player list if players joined or were kicked since the last update.
+    ///
+    /// Joins are announced together instead of one by one, so a class joining all at once doesn't fill up the queues of
+    /// the players who are already in.
+    pub fn announce_players(&mut self) {
+        if !self.lobby_changed {
+            return;
+        }
+        self.lobby_changed = false;
+        let player_names = self.player_names();
+        self.broadcast(PlayerMessage::Players { player_names });
+    }
+
+    fn player_names(&self) -> Vec<String> {
+        self.players
+            .values()
+            .map(|session_player| session_player.player.nickname.clone())
+            .collect()
+    }
+
+    /// Gives a player who joined after the start a slide to answer: the open shared slide in host-paced games, or the
+    /// first of their own otherwise.
+    fn catch_up(&mut self, player_id: &str) {
+        let Some(session_player) = self.players.get_mut(player_id) else {
+            return;
+        };
+        let Some(shared_slides) = &self.shared_slides else {
+            session_player.advance(&self.template);
+            return;
+        };
+        // Once the round has closed they wait for the next slide like everyone else
+        if let Some(current_slide) = shared_slides
+            .current_slide()
+            .filter(|current_slide| !current_slide.answered)
+        {
+            session_player.slide_stack.show(current_slide);
+            session_player.send_message(PlayerMessage::Slide {
+                slide: self.template.get_player_slide(current_slide),
+            });
+        }
     }
 
-    pub async fn answer(
+    pub fn answer(
         &mut self,
         player_id: String,
         answer: Vec<usize>,
         answer_time: Instant,
         slide_index: usize,
     ) {
-        let session_player = self.players.get_mut(&player_id).unwrap();
+        if !self.in_stage(&[GameStage::Game], &player_id) {
+            return;
+        }
+        let session_player = match self.players.get_mut(&player_id) {
+            Some(session_player) => session_player,
+            None => {
+                self.send_to(
+                    PlayerMessage::Error {
+                        err: "Not a player".to_string(),
+                    },
+                    &player_id,
+                );
+                return;
+            }
+        };
         match session_player.status {
             PlayerStatus::Connected => {
                 let current_slide = session_player.slide_stack.current_slide();
@@ -235,51 +670,77 @@ impl GameSession {
                                     err: "Incorrect slide index".to_string(),
                                 },
                                 &player_id,
-                            )
-                            .await;
+                            );
+                            return;
+                        }
+                        if current_slide.answered {
+                            self.send_to(
+                                PlayerMessage::Error {
+                                    err: "Slide already answered".to_string(),
+                                },
+                                &player_id,
+                            );
                             return;
                         }
                         let slide = &self.template.slides[current_slide.index];
-                        let elapsed_time = answer_time - current_slide.starting_time;
-                        if elapsed_time.as_secs() >= slide.duration as u64 {
+                        if answer_time >= current_slide.deadline(slide.duration) {
                             self.send_to(
                                 PlayerMessage::Error {
                                     err: "Time ran out".to_string(),
                                 },
                                 &player_id,
-                            )
-                            .await;
+                            );
                             return;
                         }
-                        let mut correct = true;
-                        let correct_answers = slide.correct_answer.clone();
-                        for correct_answer in &correct_answers {
-                            match answer
-                                .iter()
-                                .find(|player_answer| **player_answer == *correct_answer)
-                            {
-                                None => {
-                                    correct = false;
-                                    break;
-                                }
-                                _ => {}
-                            }
+                        if let Err(error) = slide.validate_answer(&answer) {
+                            self.send_to(
+                                PlayerMessage::InvalidAnswer { slide_index, error },
+                                &player_id,
+                            );
+                            return;
                         }
-                        session_player.answers.push(PlayerAnswer {
-                            player_id: player_id.clone(),
-                            slide_index,
-                            slide_start_time: current_slide.starting_time,
-                            answer,
-                            answer_submit_time: answer_time,
-                        });
-                        self.send_to(
-                            PlayerMessage::AnswerResult {
+                        let credit = slide.grade(&answer, self.settings.grading);
+                        let correct = credit >= 1.0;
+                        let correct_answers = slide.correct_answer.clone();
+                        let points = session_player.record_answer(
+                            PlayerAnswer {
+                                player_id: player_id.clone(),
+                                slide_index,
+                                slide_start_time: current_slide.starting_time,
+                                answer,
+                                answer_submit_time: answer_time,
                                 correct,
-                                correct_answers,
+                                points: 0,
                             },
-                            &player_id,
-                        )
-                        .await;
+                            true,
+                            credit,
+                            slide.duration,
+                            self.scoring.as_ref(),
+                        );
+                        session_player.slide_stack.mark_answered();
+                        if self.shared_slides.is_some() {
+                            // The rest of the room is still answering, so the result and the points wait for the reveal
+                            self.send_to(PlayerMessage::AnswerReceived { slide_index }, &player_id);
+                            if self.everyone_answered() {
+                                self.close_round();
+                            }
+                        } else {
+                            session_player.score += points;
+                            let score = session_player.score;
+                            self.standings_changed = true;
+                            self.send_to(
+                                PlayerMessage::AnswerResult {
+                                    correct,
+                                    correct_answers,
+                                    points,
+                                    score,
+                                },
+                                &player_id,
+                            );
+                            if self.settings.auto_advance {
+                                self.next_slide(player_id);
+                            }
+                        }
                     }
                     None => {
                         self.send_to(
@@ -287,8 +748,7 @@ impl GameSession {
                                 err: "No current active slide".to_string(),
                             },
                             &player_id,
-                        )
-                        .await;
+                        );
                     }
                 }
             }
@@ -298,32 +758,59 @@ impl GameSession {
                         err: "Player has already finished".to_string(),
                     },
                     &player_id,
-                )
-                .await;
+                );
             }
             PlayerStatus::Disconnected => {}
         }
     }
 
-    pub async fn next_slide(&mut self, player_id: String) {
-        let session_player = self.players.get_mut(&player_id).unwrap();
+    /// Moves the player on to the next slide in their `SlideStack`, finishing them once it runs out.
+    ///
+    /// Skipping a slide without answering counts as running out of time on it, so the empty answer is recorded and
+    /// scored the same way.
+    ///
+    /// In host-paced games only the host can move on, which takes everyone to the next slide.
+    pub fn next_slide(&mut self, player_id: String) {
+        if !self.in_stage(&[GameStage::Game], &player_id) {
+            return;
+        }
+        if self.shared_slides.is_some() {
+            if player_id == self.host.player_id {
+                self.advance_round();
+            } else {
+                self.send_to(
+                    PlayerMessage::Error {
+                        err: "The host controls the slides".to_string(),
+                    },
+                    &player_id,
+                );
+            }
+            return;
+        }
+        let session_player = match self.players.get_mut(&player_id) {
+            Some(session_player) => session_player,
+            None => {
+                self.send_to(
+                    PlayerMessage::Error {
+                        err: "Not a player".to_string(),
+                    },
+                    &player_id,
+                );
+                return;
+            }
+        };
         match session_player.status {
             PlayerStatus::Connected => {
-                let slide_index = session_player.slide_stack.next_slide();
-                if let Some(slide_index) = slide_index {
-                    session_player
-                        .player
-                        .send_message(PlayerMessage::Slide {
-                            slide: self.template.get_slide(slide_index),
-                        })
-                        .await;
-                } else {
-                    session_player.status = PlayerStatus::Finished;
-                    session_player
-                        .player
-                        .send_message(PlayerMessage::Finish)
-                        .await;
+                if let Some(current_slide) = session_player
+                    .slide_stack
+                    .current_slide()
+                    .filter(|current_slide| !current_slide.answered)
+                {
+                    self.standings_changed = true;
+                    let slide = self.template.get_slide_ref(current_slide.index);
+                    session_player.time_out(current_slide, slide, self.scoring.as_ref());
                 }
+                session_player.advance(&self.template);
             }
             PlayerStatus::Finished => {
                 self.send_to(
@@ -331,101 +818,531 @@ impl GameSession {
                         err: "Player has already finished".to_string(),
                     },
                     &player_id,
-                )
-                .await;
+                );
             }
             PlayerStatus::Disconnected => {}
         }
     }
 
-    pub async fn leave(&mut self, player_id: String) {
-        let session_player = self.players.get_mut(&player_id).unwrap();
-        session_player.status = PlayerStatus::Disconnected;
-        session_player.player.connection = None;
+    /// Returns the earliest moment a player's unfinished slide runs out of time, if any.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        if self.stage != GameStage::Game {
+            return None;
+        }
+        if let Some(shared_slides) = &self.shared_slides {
+            return shared_slides
+                .current_slide()
+                .filter(|current_slide| !current_slide.answered)
+                .map(|current_slide| {
+                    let slide = self.template.get_slide_ref(current_slide.index);
+                    current_slide.deadline(slide.duration)
+                });
+        }
+        self.players
+            .values()
+            .filter_map(|session_player| {
+                let current_slide = session_player.slide_stack.current_slide()?;
+                let slide = self.template.get_slide_ref(current_slide.index);
+                Some(current_slide.deadline(slide.duration))
+            })
+            .min()
     }
 
-    /// Kick a player from the game session
-    pub async fn kick(&mut self, kick_player_id: String, player_id: String) {
+    /// Moves every player whose slide ran out of time by `now` on to their next slide.
+    /// In host-paced games the shared slide's answers are closed instead, and the host moves on.
+    ///
+    /// Players who didn't answer in time get an empty timeout answer recorded and are sent a `TimeUp` message.
+    pub fn expire_slides(&mut self, now: Instant) {
+        if self.shared_slides.is_some() {
+            if self.next_deadline().is_some_and(|deadline| deadline <= now) {
+                self.close_round();
+            }
+            return;
+        }
+        for session_player in self.players.values_mut() {
+            let current_slide = match session_player.slide_stack.current_slide() {
+                Some(current_slide) => current_slide,
+                None => continue,
+            };
+            let slide = self.template.get_slide_ref(current_slide.index);
+            let deadline = current_slide.deadline(slide.duration);
+            if deadline > now {
+                continue;
+            }
+            if !current_slide.answered {
+                self.standings_changed = true;
+                session_player.time_out(current_slide, slide, self.scoring.as_ref());
+            }
+            session_player.advance(&self.template);
+        }
+    }
+
+    /// Closes the answers on the shared slide if the host is the one asking.
+    pub fn close_answers(&mut self, player_id: String) {
         if player_id != self.host.player_id {
             self.send_to(
                 PlayerMessage::Error {
                     err: "Not the host".to_string(),
                 },
                 &player_id,
-            )
-            .await;
+            );
             return;
         }
-        let _ = self.players.remove(&kick_player_id);
-    }
-
-    /// End the game session if player is host.
-    pub async fn host_end(&mut self, player_id: String) {
-        if player_id != self.host.player_id {
+        if !self.in_stage(&[GameStage::Game], &player_id) {
+            return;
+        }
+        if self.shared_slides.is_none() {
             self.send_to(
                 PlayerMessage::Error {
-                    err: "Not the host".to_string(),
+                    err: "Players answer at their own pace in this game".to_string(),
                 },
                 &player_id,
-            )
-            .await;
+            );
             return;
         }
-        self.end().await;
+        self.close_round();
     }
 
-    /// End the game session.
-    pub async fn end(&mut self) {
-        todo!("Add end game handling")
-    }
-
-    /// Send a message to a specific player.
-    async fn send_to(&mut self, message: PlayerMessage, player_id: &str) {
-        if player_id == self.host.player_id {
-            self.host.send_message(message).await;
+    /// Moves everyone in a host-paced game on to the next shared slide, closing the current one's answers first.
+    ///
+    /// Once the slides run out every player is finished. Does nothing once they already are.
+    fn advance_round(&mut self) {
+        if self
+            .shared_slides
+            .as_ref()
+            .is_none_or(|shared_slides| shared_slides.is_finished())
+        {
             return;
         }
-        let session_player = self.players.get_mut(player_id).unwrap();
+        self.close_round();
+        let shared_slides = match &mut self.shared_slides {
+            Some(shared_slides) => shared_slides,
+            None => return,
+        };
+        shared_slides.next_slide();
+        let current_slide = match shared_slides.current_slide() {
+            Some(current_slide) => current_slide,
+            None => {
+                let finish = Payload::new(&PlayerMessage::Finish);
+                for session_player in self.players.values_mut() {
+                    session_player.slide_stack.finish();
+                    if session_player.status == PlayerStatus::Connected {
+                        session_player.status = PlayerStatus::Finished;
+                    }
+                    session_player.deliver(finish.clone());
+                }
+                self.host.deliver(finish);
+                return;
+            }
+        };
 
-        session_player.player.send_message(message).await;
+        let player_slide = self.template.get_player_slide(current_slide);
+        let slide = Payload::new(&PlayerMessage::Slide {
+            slide: player_slide.clone(),
+        });
+        for session_player in self.players.values_mut() {
+            session_player.slide_stack.show(current_slide);
+            session_player.deliver(slide.clone());
+        }
+        self.host.send_message(PlayerMessage::HostSlide {
+            slide_index: current_slide.index,
+            deadline: player_slide.deadline,
+            slide: self.template.get_slide(current_slide.index),
+        });
     }
 
-    /// Emits a message to all participants except for one specified with player_id
-    async fn emit(&mut self, message: PlayerMessage, player_id: String) {
-        let serialized_message = serde_json::to_string(&message).unwrap();
-        if let Some(tx) = &self.host.connection {
-            println!("sent to host");
-            let _ = tx.send(Message::Text(serialized_message.clone())).await;
-        }
-        for (player_id_key, session_player) in &self.players {
-            if player_id == *player_id_key {
-                continue;
+    /// Whether every connected player has answered the shared slide.
+    fn everyone_answered(&self) -> bool {
+        self.players
+            .values()
+            .filter(|session_player| session_player.status == PlayerStatus::Connected)
+            .all(|session_player| {
+                session_player
+                    .slide_stack
+                    .current_slide()
+                    .is_none_or(|current_slide| current_slide.answered)
+            })
+    }
+
+    /// Closes the answers on the shared slide, timing out everyone who hasn't answered and revealing how everyone answered.
+    ///
+    /// Players who answered get their points and their result along with the reveal.
+    ///
+    /// Does nothing if there is no open shared slide.
+    fn close_round(&mut self) {
+        let shared_slides = match &mut self.shared_slides {
+            Some(shared_slides) => shared_slides,
+            None => return,
+        };
+        let current_slide = match shared_slides.current_slide() {
+            Some(current_slide) if !current_slide.answered => current_slide,
+            _ => return,
+        };
+        shared_slides.mark_answered();
+
+        let slide = self.template.get_slide_ref(current_slide.index);
+        let mut distribution = vec![0; slide.answers.len()];
+        let mut answered = 0;
+        for session_player in self.players.values_mut() {
+            match session_player.slide_stack.current_slide() {
+                Some(player_slide) if player_slide.index == current_slide.index => {
+                    if !player_slide.answered {
+                        self.standings_changed = true;
+                        session_player.time_out(player_slide, slide, self.scoring.as_ref());
+                        continue;
+                    }
+                }
+                _ => continue,
             }
-            if let Some(tx) = &session_player.player.connection {
-                let _ = tx.send(Message::Text(serialized_message.clone())).await;
+            if let Some(answer) = session_player
+                .answers
+                .last()
+                .filter(|answer| answer.slide_index == current_slide.index)
+            {
+                answered += 1;
+                for index in &answer.answer {
+                    distribution[*index] += 1;
+                }
+                session_player.score += answer.points;
+                self.standings_changed = true;
+                let result = PlayerMessage::AnswerResult {
+                    correct: answer.correct,
+                    correct_answers: slide.correct_answer.clone(),
+                    points: answer.points,
+                    score: session_player.score,
+                };
+                session_player.send_message(result);
             }
         }
+
+        self.broadcast(PlayerMessage::Reveal {
+            slide_index: current_slide.index,
+            correct_answers: slide.correct_answer.clone(),
+            distribution,
+            answered,
+        });
     }
 
-    /// Broadcasts a message to all participants
-    async fn broadcast(&mut self, message: PlayerMessage) {
-        let serialized_message = serde_json::to_string(&message).unwrap();
-        for session_player in self.players.values() {
-            if let Some(tx) = &session_player.player.connection {
-                let _ = tx.send(Message::Text(serialized_message.clone())).await;
+    /// Marks the player as disconnected and lets everyone else know.
+    ///
+    /// Ignored if the player has reconnected since, as the leave then comes from the connection that was replaced.
+    pub fn leave(&mut self, player_id: String, connection_id: ConnectionId) {
+        if player_id == self.host.player_id {
+            if self.host.connection_id == Some(connection_id) {
+                self.host.connection = None;
             }
+            return;
         }
+        let session_player = match self.players.get_mut(&player_id) {
+            Some(session_player) if session_player.player.connection_id == Some(connection_id) => {
+                session_player
+            }
+            _ => return,
+        };
+        session_player.status = PlayerStatus::Disconnected;
+        session_player.player.connection = None;
+        let nickname = session_player.player.nickname.clone();
+        self.emit(
+            PlayerMessage::Presence {
+                player_id: player_id.clone(),
+                nickname,
+                connected: false,
+            },
+            player_id,
+        );
     }
 
-    pub fn get_code(&self) -> u32 {
-        self.code
+    /// Everything a reconnecting player needs to pick up where they left off.
+    fn player_snapshot(&self, player_id: &str) -> Option<PlayerMessage> {
+        let session_player = self.players.get(player_id)?;
+        let current_slide = session_player.slide_stack.current_slide();
+        Some(PlayerMessage::Resume {
+            stage: self.stage,
+            slide: current_slide.map(|current_slide| self.template.get_player_slide(current_slide)),
+            remaining_time: current_slide
+                .map_or(0, |current_slide| self.remaining_time(current_slide)),
+            answered: current_slide.is_some_and(|current_slide| current_slide.answered),
+            score: session_player.score,
+            answered_count: session_player.answers.len(),
+            slide_count: self.template.get_slide_count(),
+            finished: session_player.status == PlayerStatus::Finished,
+        })
     }
-}
 
-pub enum GameStage {
-    Lobby,
-    Game,
-    End,
+    /// Everything a reconnecting host needs to rebuild their dashboard.
+    fn host_snapshot(&self) -> PlayerMessage {
+        let current_slide = self
+            .shared_slides
+            .as_ref()
+            .and_then(|shared_slides| shared_slides.current_slide());
+        PlayerMessage::HostResume {
+            stage: self.stage,
+            players: self
+                .players
+                .values()
+                .map(|session_player| PlayerProgress {
+                    player_id: session_player.player.player_id.clone(),
+                    nickname: session_player.player.nickname.clone(),
+                    connected: session_player.player.connection.is_some(),
+                    finished: session_player.slide_stack.is_finished(),
+                    score: session_player.score,
+                    answered_count: session_player.answers.len(),
+                })
+                .collect(),
+            slide_index: current_slide.map(|current_slide| current_slide.index),
+            remaining_time: current_slide
+                .filter(|current_slide| !current_slide.answered)
+                .map_or(0, |current_slide| self.remaining_time(current_slide)),
+            slide_count: self.template.get_slide_count(),
+        }
+    }
+
+    /// Milliseconds left before the slide runs out of time.
+    fn remaining_time(&self, current_slide: CurrentSlide) -> u64 {
+        let slide = self.template.get_slide_ref(current_slide.index);
+        current_slide
+            .deadline(slide.duration)
+            .saturating_duration_since(Instant::now())
+            .as_millis() as u64
+    }
+
+    /// Kick a player from the game session
+    pub fn kick(&mut self, kick_player_id: String, player_id: String) {
+        if player_id != self.host.player_id {
+            self.send_to(
+                PlayerMessage::Error {
+                    err: "Not the host".to_string(),
+                },
+                &player_id,
+            );
+            return;
+        }
+        if self.players.remove(&kick_player_id).is_some() {
+            self.standings_changed = true;
+            self.lobby_changed = true;
+        }
+    }
+
+    /// End the game session if player is host.
+    pub fn host_end(&mut self, player_id: String) {
+        if player_id != self.host.player_id {
+            self.send_to(
+                PlayerMessage::Error {
+                    err: "Not the host".to_string(),
+                },
+                &player_id,
+            );
+            return;
+        }
+        if !self.in_stage(&[GameStage::Lobby, GameStage::Game], &player_id) {
+            return;
+        }
+        self.end();
+    }
+
+    /// End the game session, sending everyone the final standings.
+    ///
+    /// An open host-paced round is closed first, so the answers already in count towards the standings.
+    pub fn end(&mut self) -> Vec<PlayerResult> {
+        self.close_round();
+        let results = self.standings();
+        self.set_stage(GameStage::End);
+        self.broadcast(PlayerMessage::GameOver {
+            results: results.clone(),
+        });
+        results
+    }
+
+    /// Lets everyone know the server is shutting down. The game's connections are dropped along with the session.
+    pub fn shutdown(&mut self) {
+        self.broadcast(PlayerMessage::ServerShutdown);
+    }
+
+    /// Sends the host the full leaderboard and every player their own rank, if anything changed since the last update.
+    pub fn update_leaderboard(&mut self) {
+        if !self.standings_changed || self.stage != GameStage::Game {
+            return;
+        }
+        self.standings_changed = false;
+
+        let standings = self.standings();
+        let player_count = standings.len();
+        let mut entries = Vec::with_capacity(player_count);
+        for result in standings {
+            let (last_rank, last_score) = self
+                .last_standings
+                .get(&result.player_id)
+                .copied()
+                .unwrap_or((player_count, 0));
+            let entry = LeaderboardEntry {
+                rank_delta: last_rank as i32 - result.rank as i32,
+                score_delta: result.score - last_score,
+                player_id: result.player_id,
+                nickname: result.nickname,
+                rank: result.rank,
+                score: result.score,
+            };
+            if let Some(session_player) = self.players.get_mut(&entry.player_id) {
+                session_player.send_message(PlayerMessage::Rank {
+                    rank: entry.rank,
+                    rank_delta: entry.rank_delta,
+                    score: entry.score,
+                    score_delta: entry.score_delta,
+                    player_count,
+                });
+            }
+            entries.push(entry);
+        }
+
+        self.last_standings = entries
+            .iter()
+            .map(|entry| (entry.player_id.clone(), (entry.rank, entry.score)))
+            .collect();
+        self.host
+            .send_message(PlayerMessage::Leaderboard { entries });
+    }
+
+    /// Ranks the players by score. Players with equal scores share a rank.
+    pub fn standings(&self) -> Vec<PlayerResult> {
+        let mut results: Vec<PlayerResult> = self
+            .players
+            .values()
+            .map(|session_player| PlayerResult {
+                player_id: session_player.player.player_id.clone(),
+                nickname: session_player.player.nickname.clone(),
+                rank: 0,
+                score: session_player.score,
+                correct_answers: session_player
+                    .answers
+                    .iter()
+                    .filter(|answer| answer.correct)
+                    .count(),
+            })
+            .collect();
+        results.sort_by(|a, b| b.score.cmp(&a.score).then(a.nickname.cmp(&b.nickname)));
+        for i in 0..results.len() {
+            results[i].rank = if i > 0 && results[i].score == results[i - 1].score {
+                results[i - 1].rank
+            } else {
+                i + 1
+            };
+        }
+        results
+    }
+
+    /// Whether the game is still running. Becomes `false` once the game has ended.
+    pub fn is_active(&self) -> bool {
+        self.stage != GameStage::End
+    }
+
+    pub fn get_stage(&self) -> GameStage {
+        self.stage
+    }
+
+    /// Moves the game to a new stage and lets everyone know.
+    fn set_stage(&mut self, stage: GameStage) {
+        self.stage = stage;
+        self.broadcast(PlayerMessage::StageChanged { stage });
+    }
+
+    /// Checks that the game is in one of the given stages, sending the player an error if it isn't.
+    fn in_stage(&mut self, stages: &[GameStage], player_id: &str) -> bool {
+        if stages.contains(&self.stage) {
+            return true;
+        }
+        let err = match self.stage {
+            GameStage::Lobby => "Game hasn't started yet",
+            GameStage::Game => "Game has already started",
+            GameStage::End => "Game has ended",
+        };
+        self.send_to(
+            PlayerMessage::Error {
+                err: err.to_string(),
+            },
+            player_id,
+        );
+        false
+    }
+
+    /// Converts the players and their answers into the form stored in the `gameSessions` document.
+    pub fn to_stored_results(&self) -> (Vec<StoredPlayer>, Vec<StoredPlayerAnswers>) {
+        let mut players = Vec::with_capacity(self.players.len());
+        let mut player_answers = vec![];
+        for session_player in self.players.values() {
+            players.push(StoredPlayer {
+                registered_player: session_player.player.registered,
+                player_id: session_player.player.player_id.clone(),
+                nickname: session_player.player.nickname.clone(),
+                score: session_player.score,
+            });
+            for answer in &session_player.answers {
+                player_answers.push(StoredPlayerAnswers {
+                    player_id: answer.player_id.clone(),
+                    question_index: answer.slide_index as u32,
+                    answers: answer.answer.iter().map(|index| *index as u32).collect(),
+                    time: (answer.answer_submit_time - answer.slide_start_time).as_millis() as u32,
+                    correct: answer.correct,
+                    points: answer.points,
+                });
+            }
+        }
+        (players, player_answers)
+    }
+
+    /// Send a message to a specific player.
+    fn send_to(&mut self, message: PlayerMessage, player_id: &str) {
+        if player_id == self.host.player_id {
+            self.host.send_message(message);
+            return;
+        }
+        if let Some(session_player) = self.players.get_mut(player_id) {
+            session_player.send_message(message);
+        }
+    }
+
+    /// Emits a message to all participants except for one specified with player_id
+    fn emit(&mut self, message: PlayerMessage, player_id: String) {
+        let payload = Payload::new(&message);
+        self.host.deliver(payload.clone());
+        for (player_id_key, session_player) in self.players.iter_mut() {
+            if player_id == *player_id_key {
+                continue;
+            }
+            session_player.deliver(payload.clone());
+        }
+    }
+
+    /// Broadcasts a message to all participants
+    fn broadcast(&mut self, message: PlayerMessage) {
+        let payload = Payload::new(&message);
+        self.host.deliver(payload.clone());
+        for session_player in self.players.values_mut() {
+            session_player.deliver(payload.clone());
+        }
+    }
+
+    pub fn get_code(&self) -> u32 {
+        self.code
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_template(&self) -> &GameTemplate {
+        &self.template
+    }
+}
+
+/// The stages a game goes through, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum GameStage {
+    /// Players are joining and waiting for the host to start.
+    Lobby,
+    /// Slides are being played.
+    Game,
+    /// The game is over and no longer accepts anything.
+    End,
 }
 
 pub struct PlayerAnswer {
@@ -434,6 +1351,41 @@ pub struct PlayerAnswer {
     slide_start_time: Instant,
     answer: Vec<usize>,
     answer_submit_time: Instant,
+    correct: bool,
+    points: i32,
+}
+
+/// A player's row on the live leaderboard. Deltas are relative to the previous leaderboard update.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    player_id: String,
+    nickname: String,
+    rank: usize,
+    /// Places gained since the last update, negative if the player dropped.
+    rank_delta: i32,
+    score: i32,
+    score_delta: i32,
+}
+
+/// How far along a player is, as shown on the host's dashboard.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PlayerProgress {
+    player_id: String,
+    nickname: String,
+    connected: bool,
+    finished: bool,
+    score: i32,
+    answered_count: usize,
+}
+
+/// A player's final placing in the game.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PlayerResult {
+    player_id: String,
+    nickname: String,
+    rank: usize,
+    score: i32,
+    correct_answers: usize,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -454,6 +1406,53 @@ impl GameTemplate {
     pub fn get_slide_count(&self) -> usize {
         self.slides.len()
     }
+
+    /// Returns the player-facing view of the current slide, without the answer key.
+    fn get_player_slide(&self, current_slide: CurrentSlide) -> PlayerSlide {
+        let slide = &self.slides[current_slide.index];
+        PlayerSlide {
+            slide_index: current_slide.index,
+            deadline: deadline_timestamp(current_slide.starting_time, slide.duration),
+            duration: slide.duration,
+            text: slide.text.clone(),
+            image: slide.image.clone(),
+            is_multiple_answer: slide.is_multiple_answer,
+            answers: slide.answers.clone(),
+        }
+    }
+
+    /// Checks that the template can be played, returning a description of the first problem found.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.slides.is_empty() {
+            return Err("template has no slides".to_string());
+        }
+        for (index, slide) in self.slides.iter().enumerate() {
+            if slide.duration == 0 {
+                return Err(format!("slide {index} has no duration"));
+            }
+            if slide.answers.is_empty() {
+                return Err(format!("slide {index} has no answers"));
+            }
+            if slide.correct_answer.is_empty() {
+                return Err(format!("slide {index} has no correct answer"));
+            }
+            if !slide.is_multiple_answer && slide.correct_answer.len() > 1 {
+                return Err(format!(
+                    "slide {index} has several correct answers but isn't multiple answer"
+                ));
+            }
+            if let Some(correct_answer) = slide
+                .correct_answer
+                .iter()
+                .find(|correct_answer| **correct_answer >= slide.answers.len())
+            {
+                return Err(format!(
+                    "slide {index} has out of range correct answer {correct_answer}"
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -466,25 +1465,119 @@ pub struct Slide {
     correct_answer: Vec<usize>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Slide {
+    /// Checks that a submission only picks existing answers, each at most once, and only one on a single answer slide.
+    fn validate_answer(&self, answer: &[usize]) -> Result<(), AnswerError> {
+        if answer.is_empty() {
+            return Err(AnswerError::Empty);
+        }
+        if !self.is_multiple_answer && answer.len() > 1 {
+            return Err(AnswerError::MultiplePicks);
+        }
+        for (i, index) in answer.iter().enumerate() {
+            if *index >= self.answers.len() {
+                return Err(AnswerError::OutOfRange { index: *index });
+            }
+            if answer[..i].contains(index) {
+                return Err(AnswerError::Duplicate { index: *index });
+            }
+        }
+        Ok(())
+    }
+
+    /// Grades a validated submission, returning the share of credit it earns between 0 and 1.
+    fn grade(&self, answer: &[usize], grading: Grading) -> f64 {
+        let correct_picks = answer
+            .iter()
+            .filter(|index| self.correct_answer.contains(index))
+            .count();
+        let wrong_picks = answer.len() - correct_picks;
+        match grading {
+            Grading::Exact => {
+                if wrong_picks == 0 && correct_picks == self.correct_answer.len() {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Grading::Partial => {
+                let credit = correct_picks as f64 - wrong_picks as f64;
+                (credit / self.correct_answer.len() as f64).max(0.0)
+            }
+        }
+    }
+}
+
+/// The slide as sent to players. Leaves out the correct answers so they never reach the client before answering.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PlayerSlide {
+    slide_index: usize,
+    /// Unix timestamp in milliseconds after which answers are no longer accepted.
+    deadline: u64,
+    duration: u8,
+    text: Option<String>,
+    image: Option<String>,
+    is_multiple_answer: bool,
+    answers: Vec<Answer>,
+}
+
+/// Converts a slide's starting `Instant` and duration into a wall clock deadline in Unix milliseconds.
+fn deadline_timestamp(starting_time: Instant, duration: u8) -> u64 {
+    let remaining = (starting_time + Duration::from_secs(duration as u64))
+        .saturating_duration_since(Instant::now());
+    (SystemTime::now() + remaining)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct StoredGameSession {
-    _id: String,
-    code: u32,
-    active: bool,
-    host: String,
-    players: Vec<StoredPlayer>,
-    template: StoredGameTemplate,
-    player_answers: Vec<StoredPlayerAnswers>,
+    pub(crate) _id: String,
+    pub(crate) code: u32,
+    pub(crate) active: bool,
+    pub(crate) host: String,
+    pub(crate) players: Vec<StoredPlayer>,
+    pub(crate) template: StoredGameTemplate,
+    pub(crate) player_answers: Vec<StoredPlayerAnswers>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct StoredGameTemplate {
-    _id: String,
-    name: String,
+impl StoredGameSession {
+    pub fn get_id(&self) -> &str {
+        &self._id
+    }
+
+    /// Replaces the stored players and answers with the ones from a finished game.
+    pub(crate) fn set_results(
+        &mut self,
+        players: Vec<StoredPlayer>,
+        player_answers: Vec<StoredPlayerAnswers>,
+    ) {
+        self.players = players;
+        self.player_answers = player_answers;
+    }
+
+    pub(crate) fn deactivate(&mut self) {
+        self.active = false;
+    }
+}
+
+#[cfg(test)]
+impl StoredGameSession {
+    /// The session in `fixtures/stored_session.json`: active, hosted by `host` and with a template without slides.
+    pub(crate) fn fixture() -> Self {
+        serde_json::from_str(include_str!("../fixtures/stored_session.json")).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct StoredGameTemplate {
+    pub(crate) _id: String,
+    pub(crate) name: String,
     tags: Vec<String>,
     slides: Vec<Slide>,
     author: String,
-    author_id: String,
+    pub(crate) author_id: String,
     flagged: bool,
     created: DateTime,
     last_updated: DateTime,
@@ -497,18 +1590,27 @@ struct Answer {
     text: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct StoredPlayer {
-    registered_player: bool,
-    player_id: String,
-    nickname: String,
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StoredPlayer {
+    pub(crate) registered_player: bool,
+    pub(crate) player_id: String,
+    pub(crate) nickname: String,
+    #[serde(default)]
+    pub(crate) score: i32,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct StoredPlayerAnswers {
-    question_index: u32,
-    answers: Vec<u32>,
-    time: u32,
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StoredPlayerAnswers {
+    #[serde(default)]
+    pub(crate) player_id: String,
+    pub(crate) question_index: u32,
+    pub(crate) answers: Vec<u32>,
+    /// Milliseconds between the slide being sent and the answer being submitted.
+    pub(crate) time: u32,
+    #[serde(default)]
+    pub(crate) correct: bool,
+    #[serde(default)]
+    pub(crate) points: i32,
 }
 
 #[derive(Deserialize)]
@@ -528,6 +1630,8 @@ pub enum Command {
         answer: Vec<usize>,
         slide_index: usize,
     },
+    Next,
+    Close,
     End,
     Leave,
     Kick {
@@ -545,24 +1649,119 @@ impl Command {
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
 #[serde(tag = "method")]
 pub enum PlayerMessage {
-    ///Serialized string containing the slide in json format
+    ///Serialized string containing the player-facing slide in json format
     Slide {
-        slide: Slide,
+        slide: PlayerSlide,
     },
+    /// Whether the answer was right. Sent on answering, or once the round closes in host-paced games.
     AnswerResult {
         correct: bool,
         correct_answers: Vec<usize>,
+        /// Points the answer was worth.
+        points: i32,
+        /// The player's total score after this answer.
+        score: i32,
+    },
+    /// The answer to a host-paced slide was recorded. Its result is sent once the round closes.
+    AnswerReceived {
+        slide_index: usize,
     },
     Error {
         err: String,
     },
+    /// Acknowledges a join with what the player was assigned, ahead of the game's state.
+    Joined {
+        player_id: String,
+        nickname: String,
+        /// Whether the player joined as the game's host.
+        host: bool,
+        stage: GameStage,
+        /// Whether the player had already joined this game before.
+        reconnected: bool,
+    },
+    /// The submitted answer was rejected and can be corrected and resubmitted.
+    InvalidAnswer {
+        slide_index: usize,
+        error: AnswerError,
+    },
+    /// Everyone in the game. Sent on joining, and to everyone whenever the list changes.
     Players {
         player_names: Vec<String>,
     },
-    PlayerJoin {
-        player_name: String,
+    /// The live standings of every player, sent to the host.
+    Leaderboard {
+        entries: Vec<LeaderboardEntry>,
+    },
+    /// The player's own standing, sent alongside the host's leaderboard.
+    Rank {
+        rank: usize,
+        rank_delta: i32,
+        score: i32,
+        score_delta: i32,
+        player_count: usize,
+    },
+    /// A player lost or regained their connection.
+    Presence {
+        player_id: String,
+        nickname: String,
+        connected: bool,
+    },
+    /// The game moved to a new stage. Also sent on joining so the client knows where the game is.
+    StageChanged {
+        stage: GameStage,
+    },
+    /// Sent to a player on reconnecting so they can resume where they left off.
+    Resume {
+        stage: GameStage,
+        /// The slide the player is on, if any.
+        slide: Option<PlayerSlide>,
+        /// Milliseconds left to answer the current slide.
+        remaining_time: u64,
+        /// Whether the current slide has already been answered.
+        answered: bool,
+        score: i32,
+        answered_count: usize,
+        slide_count: usize,
+        finished: bool,
+    },
+    /// Sent to the host on reconnecting so they can rebuild their dashboard.
+    HostResume {
+        stage: GameStage,
+        players: Vec<PlayerProgress>,
+        /// The shared slide in host-paced games.
+        slide_index: Option<usize>,
+        /// Milliseconds left before the shared slide's answers close.
+        remaining_time: u64,
+        slide_count: usize,
+    },
+    /// The game has ended, with the final standings.
+    GameOver {
+        results: Vec<PlayerResult>,
+    },
+    /// The full slide, correct answers included, sent to the host of a host-paced game.
+    HostSlide {
+        slide_index: usize,
+        /// Unix timestamp in milliseconds after which answers are no longer accepted.
+        deadline: u64,
+        slide: Slide,
+    },
+    /// The answers on a host-paced slide have closed.
+    Reveal {
+        slide_index: usize,
+        correct_answers: Vec<usize>,
+        /// How many players picked each of the slide's answers.
+        distribution: Vec<usize>,
+        /// How many players answered in time.
+        answered: usize,
+    },
+    /// The slide ran out of time before the player answered.
+    TimeUp {
+        slide_index: usize,
+        correct_answers: Vec<usize>,
     },
     Finish,
+    /// The server is going down. The game's progress so far has been saved, but the game won't continue.
+    ServerShutdown,
 }
 
 #[cfg(test)]
@@ -599,43 +1798,104 @@ mod tests {
         assert_eq!(None, slide_stack.current_slide());
     }
 
-    #[test]
-    fn game_send_to() {}
+    /// Joins a host and two players, returning their receivers with the join messages drained.
+    fn joined_session() -> (GameSession, [mpsc::Receiver<Payload>; 3]) {
+        let mut session = test_session();
+        let mut receivers = Vec::new();
+        for player_id in ["host", "player1", "player2"] {
+            let (tx, rx) = mpsc::channel(32);
+            session.join(Player::new(
+                player_id.to_string(),
+                player_id.to_string(),
+                Some(tx),
+            ));
+            receivers.push(rx);
+        }
+        for rx in receivers.iter_mut() {
+            clear_messages(rx);
+        }
+        (session, receivers.try_into().unwrap())
+    }
 
-    #[test]
-    fn game_emit() {}
+    #[tokio::test]
+    async fn game_send_to() {
+        let (mut session, [mut host_rx, mut rx1, mut rx2]) = joined_session();
 
-    #[test]
-    fn game_broadcast() {}
+        session.send_to(PlayerMessage::Finish, "player1");
+        assert_eq!(PlayerMessage::Finish, recv_player_message(&mut rx1).await);
+        assert!(host_rx.try_recv().is_err());
+        assert!(rx2.try_recv().is_err());
+
+        session.send_to(PlayerMessage::Finish, "host");
+        assert_eq!(
+            PlayerMessage::Finish,
+            recv_player_message(&mut host_rx).await
+        );
+        assert!(rx1.try_recv().is_err());
+        assert!(rx2.try_recv().is_err());
+
+        // Unknown players are ignored
+        session.send_to(PlayerMessage::Finish, "player3");
+        assert!(host_rx.try_recv().is_err());
+        assert!(rx1.try_recv().is_err());
+        assert!(rx2.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn game_emit() {
+        let (mut session, [mut host_rx, mut rx1, mut rx2]) = joined_session();
+
+        session.emit(PlayerMessage::Finish, "player1".to_string());
+        assert_eq!(
+            PlayerMessage::Finish,
+            recv_player_message(&mut host_rx).await
+        );
+        assert_eq!(PlayerMessage::Finish, recv_player_message(&mut rx2).await);
+        assert!(rx1.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn game_broadcast() {
+        let (mut session, mut receivers) = joined_session();
+
+        session.broadcast(PlayerMessage::Finish);
+        for rx in receivers.iter_mut() {
+            assert_eq!(PlayerMessage::Finish, recv_player_message(rx).await);
+            assert!(rx.try_recv().is_err());
+        }
+    }
 
     #[tokio::test]
     async fn game_join() {
-        let mut session = GameSession::from_stored(StoredGameSession {
-            _id: "".to_string(),
-            code: 012345,
-            active: true,
-            host: "1".to_string(),
-            players: vec![],
-            template: StoredGameTemplate {
+        let mut session = GameSession::from_stored(
+            StoredGameSession {
                 _id: "".to_string(),
-                name: "".to_string(),
-                tags: vec![],
-                slides: vec![],
-                author: "".to_string(),
-                author_id: "".to_string(),
-                flagged: false,
-                created: DateTime::now(),
-                last_updated: DateTime::now(),
-                public: true,
+                code: 12345,
+                active: true,
+                host: "1".to_string(),
+                players: vec![],
+                template: StoredGameTemplate {
+                    _id: "".to_string(),
+                    name: "".to_string(),
+                    tags: vec![],
+                    slides: vec![],
+                    author: "".to_string(),
+                    author_id: "".to_string(),
+                    flagged: false,
+                    created: DateTime::now(),
+                    last_updated: DateTime::now(),
+                    public: true,
+                },
+                player_answers: vec![],
             },
-            player_answers: vec![],
-        });
+            GameSettings::default(),
+        );
         // Tests that the player list is indeed empty in the beginning
         assert_eq!(0, session.players.keys().len());
         let mut player = Player::new("player1".to_string(), "player1".to_string(), None);
         
         // Initial player join without the connection part
-        session.join(player.clone()).await;
+        session.join(player.clone());
         assert_eq!(1, session.players.keys().len());
 
         let session_player = session.players.get("player1").unwrap();
@@ -646,11 +1906,19 @@ mod tests {
         assert_eq!(player.player_id, game_player.player_id);
 
         // Player has connection and is updated ingame with the connection
-        let (tx, mut rx) = mpsc::channel(8);
-        // Player receives one message which should be PlayerMessage::Players with all the players in the session  
+        let (tx, mut rx) = mpsc::channel::<Payload>(8);
+        // Player receives an acknowledgement followed by PlayerMessage::Players with all the players in the session  
         let receive_players = tokio::task::spawn(async move {
-            let msg: Message = rx.recv().await.unwrap();
-            assert!(matches!(Message::Text, msg));
+            // The join is acknowledged first
+            assert!(matches!(
+                recv_player_message(&mut rx).await,
+                PlayerMessage::Joined {
+                    reconnected: true,
+                    ..
+                }
+            ));
+            let msg: Message = rx.recv().await.unwrap().into();
+            assert!(matches!(msg, Message::Text(_)));
             let text = msg.to_text().unwrap();
             let player_message = serde_json::from_value(serde_json::from_str(text).unwrap()).unwrap();
             assert_eq!(PlayerMessage::Players { player_names: vec!["player1".to_string()] }, player_message);
@@ -658,7 +1926,7 @@ mod tests {
         player.new_connection(tx);
 
         // Number of players in game should still be 1
-        session.join(player.clone()).await;
+        session.join(player.clone());
         assert_eq!(1, session.players.keys().len());
 
         let session_player = session.players.get("player1").unwrap();
@@ -670,6 +1938,1131 @@ mod tests {
         receive_players.await.unwrap();
     }
 
+    fn test_template() -> GameTemplate {
+        let stored_template: StoredGameTemplate =
+            serde_json::from_value(serde_json::from_str(TEST_TEMPLATE).unwrap()).unwrap();
+        GameTemplate {
+            name: stored_template.name,
+            slides: stored_template.slides,
+            author: stored_template.author,
+            author_id: stored_template.author_id,
+        }
+    }
+
+    fn test_session() -> GameSession {
+        test_session_with(GameSettings::default())
+    }
+
+    fn test_session_with(settings: GameSettings) -> GameSession {
+        let stored_template: StoredGameTemplate =
+            serde_json::from_value(serde_json::from_str(TEST_TEMPLATE).unwrap()).unwrap();
+        GameSession::from_stored(
+            StoredGameSession {
+                _id: "session".to_string(),
+                code: 12345,
+                active: true,
+                host: "host".to_string(),
+                players: vec![],
+                template: stored_template,
+                player_answers: vec![],
+            },
+            settings,
+        )
+    }
+
+    /// Drops every message the player has been sent so far.
+    fn clear_messages(rx: &mut mpsc::Receiver<Payload>) {
+        while rx.try_recv().is_ok() {}
+    }
+
+    /// Receives the next message sent to a player and parses it into a `PlayerMessage`.
+    async fn recv_player_message(rx: &mut mpsc::Receiver<Payload>) -> PlayerMessage {
+        let msg = rx.recv().await.unwrap();
+        serde_json::from_str(msg.as_str()).unwrap()
+    }
+
+    /// Skips the join messages until the player is sent a slide, which has to be the given one.
+    async fn clear_messages_until_slide(rx: &mut mpsc::Receiver<Payload>, slide_index: usize) {
+        loop {
+            match recv_player_message(rx).await {
+                PlayerMessage::Slide { slide } => {
+                    assert_eq!(slide_index, slide.slide_index);
+                    return;
+                }
+                PlayerMessage::Joined { .. }
+                | PlayerMessage::Players { .. }
+                | PlayerMessage::StageChanged { .. } => {}
+                message => panic!("expected a slide, got {message:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn game_next_slide() {
+        let mut session = test_session();
+        let (tx, mut rx) = mpsc::channel(8);
+        let player = Player::new("player1".to_string(), "player1".to_string(), Some(tx));
+        assert!(session.join(player));
+        assert_eq!(
+            PlayerMessage::Joined {
+                player_id: "player1".to_string(),
+                nickname: "player1".to_string(),
+                host: false,
+                stage: GameStage::Lobby,
+                reconnected: false,
+            },
+            recv_player_message(&mut rx).await
+        );
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Players { .. }
+        ));
+        assert_eq!(
+            PlayerMessage::StageChanged {
+                stage: GameStage::Lobby
+            },
+            recv_player_message(&mut rx).await
+        );
+        assert_eq!(PlayerStatus::Connected, session.players["player1"].status);
+
+        session.start("host".to_string());
+        assert_eq!(
+            PlayerMessage::StageChanged {
+                stage: GameStage::Game
+            },
+            recv_player_message(&mut rx).await
+        );
+        let mut seen_slides = vec![];
+        match recv_player_message(&mut rx).await {
+            PlayerMessage::Slide { slide } => seen_slides.push(slide.slide_index),
+            message => panic!("expected a slide, got {message:?}"),
+        }
+        for _ in 0..2 {
+            session.next_slide("player1".to_string());
+            // The slides are skipped without answering, which counts as running out of time
+            assert!(matches!(
+                recv_player_message(&mut rx).await,
+                PlayerMessage::TimeUp { .. }
+            ));
+            match recv_player_message(&mut rx).await {
+                PlayerMessage::Slide { slide } => seen_slides.push(slide.slide_index),
+                message => panic!("expected a slide, got {message:?}"),
+            }
+        }
+        seen_slides.sort();
+        assert_eq!(vec![0, 1, 2], seen_slides);
+
+        session.next_slide("player1".to_string());
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::TimeUp { .. }
+        ));
+        assert_eq!(PlayerMessage::Finish, recv_player_message(&mut rx).await);
+        let session_player = session.players.get("player1").unwrap();
+        assert_eq!(PlayerStatus::Finished, session_player.status);
+        // Every skipped slide still has an answer stored
+        assert_eq!(3, session_player.answers.len());
+        assert!(session_player
+            .answers
+            .iter()
+            .all(|answer| answer.answer.is_empty() && !answer.correct));
+    }
+
+    #[tokio::test]
+    async fn game_expire_slides() {
+        let mut session = test_session();
+        let (tx, mut rx) = mpsc::channel(8);
+        let player = Player::new("player1".to_string(), "player1".to_string(), Some(tx));
+        session.join(player.clone());
+        session.join(player);
+        clear_messages(&mut rx);
+        assert_eq!(None, session.next_deadline());
+
+        session.start("host".to_string());
+        let _ = recv_player_message(&mut rx).await;
+        let first_slide = match recv_player_message(&mut rx).await {
+            PlayerMessage::Slide { slide } => slide.slide_index,
+            message => panic!("expected a slide, got {message:?}"),
+        };
+        let deadline = session.next_deadline().unwrap();
+
+        // Nothing expires before the deadline
+        session.expire_slides(deadline - Duration::from_millis(1));
+        assert!(rx.try_recv().is_err());
+
+        session.expire_slides(deadline);
+        match recv_player_message(&mut rx).await {
+            PlayerMessage::TimeUp { slide_index, .. } => assert_eq!(first_slide, slide_index),
+            message => panic!("expected time up, got {message:?}"),
+        }
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Slide { .. }
+        ));
+        let session_player = session.players.get("player1").unwrap();
+        assert_eq!(1, session_player.answers.len());
+        assert!(session_player.answers[0].answer.is_empty());
+
+        // Running out of time on every slide finishes the player
+        for _ in 0..2 {
+            session.expire_slides(Instant::now() + Duration::from_secs(60));
+        }
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::TimeUp { .. }
+        ));
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Slide { .. }
+        ));
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::TimeUp { .. }
+        ));
+        assert_eq!(PlayerMessage::Finish, recv_player_message(&mut rx).await);
+        assert_eq!(None, session.next_deadline());
+    }
+
+    #[tokio::test]
+    async fn game_end() {
+        let mut session = test_session();
+        let (host_tx, mut host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        for player_id in ["player1", "player2", "player3"] {
+            session.join(Player::new(
+                player_id.to_string(),
+                player_id.to_string(),
+                None,
+            ));
+        }
+        session.start("host".to_string());
+        // player1 and player2 answer their first slide correctly, player3 runs out of time
+        for player_id in ["player1", "player2"] {
+            let current_slide = session.players[player_id]
+                .slide_stack
+                .current_slide()
+                .unwrap();
+            let correct_answer = session.template.slides[current_slide.index]
+                .correct_answer
+                .clone();
+            let session_player = session.players.get_mut(player_id).unwrap();
+            session_player.status = PlayerStatus::Connected;
+            session.answer(
+                player_id.to_string(),
+                correct_answer,
+                Instant::now(),
+                current_slide.index,
+            );
+        }
+        session.expire_slides(Instant::now() + Duration::from_secs(60));
+
+        clear_messages(&mut host_rx);
+        let results = session.end();
+        assert!(!session.is_active());
+        assert_eq!(
+            PlayerMessage::StageChanged {
+                stage: GameStage::End
+            },
+            recv_player_message(&mut host_rx).await
+        );
+        assert_eq!(
+            PlayerMessage::GameOver {
+                results: results.clone()
+            },
+            recv_player_message(&mut host_rx).await
+        );
+
+        let ranks: Vec<(usize, i32)> = results.iter().map(|r| (r.rank, r.score)).collect();
+        assert_eq!(vec![(1, 1000), (1, 1000), (3, 0)], ranks);
+        assert_eq!("player3", results[2].player_id);
+
+        let (players, player_answers) = session.to_stored_results();
+        assert_eq!(3, players.len());
+        // Two correct answers and player3's timeout
+        assert_eq!(3, player_answers.len());
+        assert_eq!(
+            2,
+            player_answers
+                .iter()
+                .filter(|answer| answer.correct)
+                .count()
+        );
+    }
+
+    #[tokio::test]
+    async fn game_update_leaderboard() {
+        let mut session = test_session();
+        let (host_tx, mut host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        let (tx, mut rx) = mpsc::channel(8);
+        let player = Player::new("player1".to_string(), "player1".to_string(), Some(tx));
+        session.join(player);
+        session.join(Player::new(
+            "player2".to_string(),
+            "player2".to_string(),
+            None,
+        ));
+        session.start("host".to_string());
+        clear_messages(&mut host_rx);
+        clear_messages(&mut rx);
+
+        session.update_leaderboard();
+        let _ = recv_player_message(&mut host_rx).await;
+        let _ = recv_player_message(&mut rx).await;
+
+        let current_slide = session.players["player1"]
+            .slide_stack
+            .current_slide()
+            .unwrap();
+        let correct_answer = session.template.slides[current_slide.index]
+            .correct_answer
+            .clone();
+        session.answer(
+            "player1".to_string(),
+            correct_answer,
+            Instant::now(),
+            current_slide.index,
+        );
+        let _ = recv_player_message(&mut rx).await;
+
+        session.update_leaderboard();
+        match recv_player_message(&mut host_rx).await {
+            PlayerMessage::Leaderboard { entries } => {
+                assert_eq!(2, entries.len());
+                assert_eq!("player1", entries[0].player_id);
+                assert_eq!(1, entries[0].rank);
+                assert_eq!(1000, entries[0].score_delta);
+                assert_eq!(0, entries[0].rank_delta);
+                assert_eq!(2, entries[1].rank);
+                assert_eq!(-1, entries[1].rank_delta);
+            }
+            message => panic!("expected a leaderboard, got {message:?}"),
+        }
+        assert_eq!(
+            PlayerMessage::Rank {
+                rank: 1,
+                rank_delta: 0,
+                score: 1000,
+                score_delta: 1000,
+                player_count: 2,
+            },
+            recv_player_message(&mut rx).await
+        );
+
+        // Nothing is resent while the standings stay the same
+        session.update_leaderboard();
+        assert!(host_rx.try_recv().is_err());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn game_host_paced() {
+        let mut session = test_session_with(GameSettings {
+            mode: GameMode::HostPaced,
+            ..Default::default()
+        });
+        let (host_tx, mut host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        let (tx, mut rx) = mpsc::channel(8);
+        let player = Player::new("player1".to_string(), "player1".to_string(), Some(tx));
+        session.join(player.clone());
+        session.join(player);
+        // player2 never connects, so the round doesn't wait for them
+        session.join(Player::new(
+            "player2".to_string(),
+            "player2".to_string(),
+            None,
+        ));
+        clear_messages(&mut host_rx);
+        clear_messages(&mut rx);
+
+        // Everyone gets the first slide of the template together
+        session.start("host".to_string());
+        let _ = recv_player_message(&mut rx).await;
+        let _ = recv_player_message(&mut host_rx).await;
+        match recv_player_message(&mut rx).await {
+            PlayerMessage::Slide { slide } => assert_eq!(0, slide.slide_index),
+            message => panic!("expected a slide, got {message:?}"),
+        }
+        match recv_player_message(&mut host_rx).await {
+            PlayerMessage::HostSlide {
+                slide_index, slide, ..
+            } => {
+                assert_eq!(0, slide_index);
+                assert_eq!(vec![0], slide.correct_answer);
+            }
+            message => panic!("expected a host slide, got {message:?}"),
+        }
+        assert_eq!(
+            Some(0),
+            session.players["player2"]
+                .slide_stack
+                .current_slide()
+                .map(|current_slide| current_slide.index)
+        );
+
+        // Players can't move on by themselves
+        session.next_slide("player1".to_string());
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Error { .. }
+        ));
+
+        // The last connected player answering closes the round and reveals the answers
+        session.answer("player1".to_string(), vec![1], Instant::now(), 0);
+        assert_eq!(
+            PlayerMessage::AnswerReceived { slide_index: 0 },
+            recv_player_message(&mut rx).await
+        );
+        assert_eq!(
+            PlayerMessage::AnswerResult {
+                correct: false,
+                correct_answers: vec![0],
+                points: 0,
+                score: 0,
+            },
+            recv_player_message(&mut rx).await
+        );
+        let reveal = PlayerMessage::Reveal {
+            slide_index: 0,
+            correct_answers: vec![0],
+            distribution: vec![0, 1],
+            answered: 1,
+        };
+        assert_eq!(reveal, recv_player_message(&mut rx).await);
+        assert_eq!(reveal, recv_player_message(&mut host_rx).await);
+        assert_eq!(1, session.players["player2"].answers.len());
+        assert_eq!(None, session.next_deadline());
+
+        // The next round closes once its time runs out
+        session.next_slide("host".to_string());
+        match recv_player_message(&mut rx).await {
+            PlayerMessage::Slide { slide } => assert_eq!(1, slide.slide_index),
+            message => panic!("expected a slide, got {message:?}"),
+        }
+        let _ = recv_player_message(&mut host_rx).await;
+        let deadline = session.next_deadline().unwrap();
+        session.expire_slides(deadline);
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::TimeUp { slide_index: 1, .. }
+        ));
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Reveal { answered: 0, .. }
+        ));
+        let _ = recv_player_message(&mut host_rx).await;
+
+        // Moving past the last slide finishes everyone
+        session.next_slide("host".to_string());
+        let _ = recv_player_message(&mut rx).await;
+        let _ = recv_player_message(&mut host_rx).await;
+        session.close_answers("host".to_string());
+        let _ = recv_player_message(&mut rx).await;
+        let _ = recv_player_message(&mut rx).await;
+        let _ = recv_player_message(&mut host_rx).await;
+        session.next_slide("host".to_string());
+        assert_eq!(PlayerMessage::Finish, recv_player_message(&mut rx).await);
+        assert_eq!(
+            PlayerMessage::Finish,
+            recv_player_message(&mut host_rx).await
+        );
+        assert_eq!(PlayerStatus::Finished, session.players["player1"].status);
+        assert_eq!(3, session.players["player1"].answers.len());
+
+        // Moving on again doesn't finish anyone a second time
+        session.next_slide("host".to_string());
+        assert!(rx.try_recv().is_err());
+        assert!(host_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn game_host_paced_holds_back_points() {
+        let mut session = test_session_with(GameSettings {
+            mode: GameMode::HostPaced,
+            ..Default::default()
+        });
+        let (host_tx, mut host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        let (tx, mut rx) = mpsc::channel(8);
+        session.join(Player::new(
+            "player1".to_string(),
+            "player1".to_string(),
+            Some(tx),
+        ));
+        let (other_tx, _other_rx) = mpsc::channel(8);
+        session.join(Player::new(
+            "player2".to_string(),
+            "player2".to_string(),
+            Some(other_tx),
+        ));
+        session.start("host".to_string());
+        session.update_leaderboard();
+        clear_messages(&mut host_rx);
+        clear_messages(&mut rx);
+
+        // A correct answer doesn't show up in the player's rank while player2 is still answering
+        session.answer("player1".to_string(), vec![0], Instant::now(), 0);
+        assert_eq!(
+            PlayerMessage::AnswerReceived { slide_index: 0 },
+            recv_player_message(&mut rx).await
+        );
+        session.update_leaderboard();
+        assert!(rx.try_recv().is_err());
+        assert_eq!(0, session.players["player1"].score);
+
+        // The points count once the round is revealed
+        session.close_answers("host".to_string());
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::AnswerResult {
+                correct: true,
+                score: 1000,
+                ..
+            }
+        ));
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Reveal { .. }
+        ));
+        session.update_leaderboard();
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Rank {
+                score: 1000,
+                score_delta: 1000,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn game_stages() {
+        let mut session = test_session();
+        let (tx, mut rx) = mpsc::channel(8);
+        let player = Player::new("player1".to_string(), "player1".to_string(), Some(tx));
+        session.join(player.clone());
+        session.join(player.clone());
+        clear_messages(&mut rx);
+        assert_eq!(GameStage::Lobby, session.get_stage());
+
+        // Nothing can be answered before the game starts
+        session.answer("player1".to_string(), vec![0], Instant::now(), 0);
+        assert_eq!(
+            PlayerMessage::Error {
+                err: "Game hasn't started yet".to_string()
+            },
+            recv_player_message(&mut rx).await
+        );
+        assert!(session.players["player1"].answers.is_empty());
+
+        session.start("host".to_string());
+        assert_eq!(GameStage::Game, session.get_stage());
+        clear_messages(&mut rx);
+
+        // Starting again doesn't reshuffle anything
+        let current_slide = session.players["player1"].slide_stack.current_slide();
+        session.start("host".to_string());
+        assert_eq!(
+            current_slide,
+            session.players["player1"].slide_stack.current_slide()
+        );
+
+        session.host_end("host".to_string());
+        assert_eq!(GameStage::End, session.get_stage());
+        clear_messages(&mut rx);
+
+        // Joining after the end is rejected
+        session.join(player);
+        assert_eq!(
+            PlayerMessage::Error {
+                err: "Game has ended".to_string()
+            },
+            recv_player_message(&mut rx).await
+        );
+        assert_eq!(None, session.next_deadline());
+    }
+
+    #[tokio::test]
+    async fn game_reconnect() {
+        let mut session = test_session();
+        let mut player = Player::new("player1".to_string(), "player1".to_string(), None);
+        let (tx, _rx) = mpsc::channel(32);
+        let connection_id = player.new_connection(tx);
+        session.join(player.clone());
+        session.start("host".to_string());
+        let current_slide = session.players["player1"]
+            .slide_stack
+            .current_slide()
+            .unwrap();
+        let correct_answer = session.template.slides[current_slide.index]
+            .correct_answer
+            .clone();
+        session.players.get_mut("player1").unwrap().status = PlayerStatus::Connected;
+        session.answer(
+            "player1".to_string(),
+            correct_answer,
+            Instant::now(),
+            current_slide.index,
+        );
+        session.leave("player1".to_string(), connection_id);
+        assert_eq!(
+            PlayerStatus::Disconnected,
+            session.players["player1"].status
+        );
+
+        // The player gets everything they need to carry on after the acknowledgement, names and stage
+        let (tx, mut rx) = mpsc::channel(8);
+        let mut reconnecting = player.clone();
+        reconnecting.new_connection(tx);
+        session.join(reconnecting);
+        assert_eq!(PlayerStatus::Connected, session.players["player1"].status);
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Joined {
+                reconnected: true,
+                ..
+            }
+        ));
+        let _ = recv_player_message(&mut rx).await;
+        let _ = recv_player_message(&mut rx).await;
+        match recv_player_message(&mut rx).await {
+            PlayerMessage::Resume {
+                stage,
+                slide,
+                remaining_time,
+                answered,
+                score,
+                answered_count,
+                slide_count,
+                finished,
+            } => {
+                assert_eq!(GameStage::Game, stage);
+                assert_eq!(current_slide.index, slide.unwrap().slide_index);
+                assert!(remaining_time > 0 && remaining_time <= 10_000);
+                assert!(answered);
+                assert_eq!(1000, score);
+                assert_eq!(1, answered_count);
+                assert_eq!(3, slide_count);
+                assert!(!finished);
+            }
+            message => panic!("expected a resume snapshot, got {message:?}"),
+        }
+
+        // The host's first connection isn't a reconnect and has nothing to resume
+        let (host_tx, mut host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        assert_eq!(Payload::from("hostJoin"), host_rx.recv().await.unwrap());
+        assert!(matches!(
+            recv_player_message(&mut host_rx).await,
+            PlayerMessage::Joined {
+                host: true,
+                reconnected: false,
+                ..
+            }
+        ));
+        let _ = recv_player_message(&mut host_rx).await;
+        let _ = recv_player_message(&mut host_rx).await;
+        assert!(host_rx.try_recv().is_err());
+
+        // A returning host gets a dashboard of every player
+        let (host_tx, mut host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        assert_eq!(Payload::from("hostJoin"), host_rx.recv().await.unwrap());
+        assert!(matches!(
+            recv_player_message(&mut host_rx).await,
+            PlayerMessage::Joined {
+                host: true,
+                reconnected: true,
+                ..
+            }
+        ));
+        let _ = recv_player_message(&mut host_rx).await;
+        let _ = recv_player_message(&mut host_rx).await;
+        match recv_player_message(&mut host_rx).await {
+            PlayerMessage::HostResume {
+                stage,
+                players,
+                slide_index,
+                ..
+            } => {
+                assert_eq!(GameStage::Game, stage);
+                assert_eq!(None, slide_index);
+                assert_eq!(1, players.len());
+                assert!(players[0].connected);
+                assert_eq!(1000, players[0].score);
+            }
+            message => panic!("expected a host resume snapshot, got {message:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn game_join_announced_once() {
+        let mut session = test_session();
+        let (tx, mut rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "player1".to_string(),
+            "player1".to_string(),
+            Some(tx),
+        ));
+        session.announce_players();
+        clear_messages(&mut rx);
+
+        // A whole class joining at once is announced in a single message
+        for index in 2..=40 {
+            session.join(Player::new(
+                format!("player{index}"),
+                format!("player{index}"),
+                None,
+            ));
+        }
+        assert!(rx.try_recv().is_err());
+        session.announce_players();
+        match recv_player_message(&mut rx).await {
+            PlayerMessage::Players { player_names } => assert_eq!(40, player_names.len()),
+            message => panic!("expected the players, got {message:?}"),
+        }
+        assert_eq!(PlayerStatus::Connected, session.players["player1"].status);
+
+        // The host and a returning player aren't added to the lobby again
+        let (host_tx, _host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        session.join(Player::new(
+            "player2".to_string(),
+            "player2".to_string(),
+            None,
+        ));
+        session.announce_players();
+        while let Ok(msg) = rx.try_recv() {
+            let message: PlayerMessage = serde_json::from_str(msg.as_str()).unwrap();
+            assert!(
+                !matches!(message, PlayerMessage::Players { .. }),
+                "unexpected {message:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn game_leave_presence() {
+        let mut session = test_session();
+        let (host_tx, mut host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        let mut player = Player::new("player1".to_string(), "player1".to_string(), None);
+        let (tx, _rx) = mpsc::channel(32);
+        let connection_id = player.new_connection(tx);
+        session.join(player);
+        clear_messages(&mut host_rx);
+
+        session.leave("player1".to_string(), connection_id);
+        assert_eq!(
+            PlayerStatus::Disconnected,
+            session.players["player1"].status
+        );
+        assert_eq!(
+            PlayerMessage::Presence {
+                player_id: "player1".to_string(),
+                nickname: "player1".to_string(),
+                connected: false,
+            },
+            recv_player_message(&mut host_rx).await
+        );
+
+        // Leaving twice or leaving as someone unknown doesn't panic
+        session.leave("player1".to_string(), connection_id);
+        session.leave("nobody".to_string(), connection_id);
+        let host_connection = session.host.connection_id.unwrap();
+        session.leave("host".to_string(), host_connection);
+        assert!(session.host.connection.is_none());
+    }
+
+    #[tokio::test]
+    async fn game_leave_replaced_connection() {
+        let mut session = test_session();
+        let mut host = Player::new("host".to_string(), "Host".to_string(), None);
+        let (host_tx_a, _host_rx_a) = mpsc::channel(32);
+        let host_a = host.new_connection(host_tx_a);
+        session.join(host.clone());
+        let (host_tx_b, _host_rx_b) = mpsc::channel(32);
+        let host_b = host.new_connection(host_tx_b);
+        session.join(host);
+
+        let mut player = Player::new("player1".to_string(), "player1".to_string(), None);
+        let (tx_a, _rx_a) = mpsc::channel(32);
+        let connection_a = player.new_connection(tx_a);
+        session.join(player.clone());
+        let (tx_b, mut rx_b) = mpsc::channel(32);
+        let connection_b = player.new_connection(tx_b);
+        session.join(player);
+        clear_messages(&mut rx_b);
+
+        // The old connections closing after the reconnect don't take the new ones down with them
+        session.leave("player1".to_string(), connection_a);
+        session.leave("host".to_string(), host_a);
+        let session_player = &session.players["player1"];
+        assert_eq!(PlayerStatus::Connected, session_player.status);
+        assert!(session_player.player.connection.is_some());
+        assert!(session.host.connection.is_some());
+        session.broadcast(PlayerMessage::Finish);
+        assert_eq!(PlayerMessage::Finish, recv_player_message(&mut rx_b).await);
+
+        session.leave("player1".to_string(), connection_b);
+        session.leave("host".to_string(), host_b);
+        assert_eq!(
+            PlayerStatus::Disconnected,
+            session.players["player1"].status
+        );
+        assert!(session.host.connection.is_none());
+    }
+
+    #[tokio::test]
+    async fn game_late_join() {
+        let mut session = test_session();
+        session.start("host".to_string());
+
+        let (tx, mut rx) = mpsc::channel(8);
+        session.join(Player::new(
+            "late".to_string(),
+            "late".to_string(),
+            Some(tx),
+        ));
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Joined {
+                stage: GameStage::Game,
+                reconnected: false,
+                ..
+            }
+        ));
+        let _ = recv_player_message(&mut rx).await;
+        let _ = recv_player_message(&mut rx).await;
+        // They start on their own first slide instead of waiting for a Next they don't know to send
+        let current_slide = session.players["late"].slide_stack.current_slide().unwrap();
+        match recv_player_message(&mut rx).await {
+            PlayerMessage::Slide { slide } => assert_eq!(current_slide.index, slide.slide_index),
+            message => panic!("expected a slide, got {message:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn game_late_join_host_paced() {
+        let mut session = test_session_with(GameSettings {
+            mode: GameMode::HostPaced,
+            ..Default::default()
+        });
+        session.start("host".to_string());
+
+        let (tx, mut rx) = mpsc::channel(8);
+        session.join(Player::new(
+            "late".to_string(),
+            "late".to_string(),
+            Some(tx),
+        ));
+        clear_messages_until_slide(&mut rx, 0).await;
+        session.answer("late".to_string(), vec![0], Instant::now(), 0);
+        assert_eq!(
+            PlayerMessage::AnswerReceived { slide_index: 0 },
+            recv_player_message(&mut rx).await
+        );
+
+        // Joining after the round closed waits for the next slide
+        session.close_answers("host".to_string());
+        let (tx, mut rx) = mpsc::channel(8);
+        session.join(Player::new(
+            "later".to_string(),
+            "later".to_string(),
+            Some(tx),
+        ));
+        for _ in 0..3 {
+            let _ = recv_player_message(&mut rx).await;
+        }
+        assert!(rx.try_recv().is_err());
+        session.next_slide("host".to_string());
+        clear_messages_until_slide(&mut rx, 1).await;
+    }
+
+    #[tokio::test]
+    async fn game_idle_timeout_after_reconnect() {
+        let mut session = test_session();
+        let (host_tx, mut host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        let mut player = Player::new("player1".to_string(), "player1".to_string(), None);
+        let (tx_a, _rx_a) = mpsc::channel(32);
+        let stale_connection = player.new_connection(tx_a);
+        session.join(player.clone());
+        session.start("host".to_string());
+        let (tx_b, mut rx_b) = mpsc::channel(32);
+        player.new_connection(tx_b);
+        session.join(player);
+        clear_messages(&mut host_rx);
+        clear_messages(&mut rx_b);
+
+        // The old socket only times out after the reconnect, which nobody should hear about
+        session.leave("player1".to_string(), stale_connection);
+        assert_eq!(PlayerStatus::Connected, session.players["player1"].status);
+        assert!(host_rx.try_recv().is_err());
+
+        // The new connection keeps playing
+        session.next_slide("player1".to_string());
+        assert!(matches!(
+            recv_player_message(&mut rx_b).await,
+            PlayerMessage::TimeUp { .. }
+        ));
+        assert!(matches!(
+            recv_player_message(&mut rx_b).await,
+            PlayerMessage::Slide { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn game_slow_consumer() {
+        let mut session = test_session();
+        let (host_tx, mut host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        let slow_consumers = metrics::slow_consumers();
+        let dropped_messages = metrics::dropped_messages();
+        // A client that never reads its messages
+        let (slow_tx, _slow_rx) = mpsc::channel(1);
+        session.join(Player::new(
+            "slow".to_string(),
+            "slow".to_string(),
+            Some(slow_tx),
+        ));
+        clear_messages(&mut host_rx);
+
+        for _ in 0..10 {
+            session.broadcast(PlayerMessage::Finish);
+        }
+        let slow_player = &session.players["slow"];
+        assert_eq!(PlayerStatus::Disconnected, slow_player.status);
+        assert!(slow_player.player.connection.is_none());
+        // The client is dropped once, but misses the join's players and stage as well as every broadcast. Other tests
+        // can add to the counters while this one runs, so only lower bounds hold
+        assert!(metrics::slow_consumers() - slow_consumers >= 1);
+        assert!(metrics::dropped_messages() - dropped_messages >= 12);
+
+        // Everyone else keeps getting their messages
+        for _ in 0..10 {
+            assert_eq!(
+                PlayerMessage::Finish,
+                recv_player_message(&mut host_rx).await
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn game_broadcast_shares_payload() {
+        let mut session = test_session();
+        let (host_tx, mut host_rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "host".to_string(),
+            "Host".to_string(),
+            Some(host_tx),
+        ));
+        let (tx, mut rx) = mpsc::channel(32);
+        session.join(Player::new(
+            "player1".to_string(),
+            "player1".to_string(),
+            Some(tx),
+        ));
+        clear_messages(&mut host_rx);
+        clear_messages(&mut rx);
+
+        session.broadcast(PlayerMessage::Finish);
+        let host_payload = host_rx.recv().await.unwrap();
+        let player_payload = rx.recv().await.unwrap();
+        // Serialized once and shared instead of copied for every connection
+        assert!(Arc::ptr_eq(&host_payload.0, &player_payload.0));
+        assert_eq!(
+            Message::Text(r#"{"method":"Finish"}"#.to_string()),
+            Message::from(player_payload)
+        );
+    }
+
+    #[tokio::test]
+    async fn game_guests() {
+        let mut session = test_session();
+        let (tx, mut rx) = mpsc::channel(8);
+        assert!(session.join(Player::guest(
+            "guest:1".to_string(),
+            "guest".to_string(),
+            Some(tx),
+        )));
+        session.join(Player::new(
+            "player1".to_string(),
+            "player1".to_string(),
+            None,
+        ));
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Joined { .. }
+        ));
+
+        let (players, _) = session.to_stored_results();
+        for player in players {
+            assert_eq!(player.player_id != "guest:1", player.registered_player);
+        }
+
+        // Hosts can keep guests out
+        let mut session = test_session_with(GameSettings {
+            forbid_guests: true,
+            ..GameSettings::default()
+        });
+        let (tx, mut rx) = mpsc::channel(8);
+        assert!(!session.join(Player::guest(
+            "guest:1".to_string(),
+            "guest".to_string(),
+            Some(tx),
+        )));
+        assert!(session.players.is_empty());
+        assert!(matches!(
+            recv_player_message(&mut rx).await,
+            PlayerMessage::Error { .. }
+        ));
+        assert!(session.join(Player::new(
+            "player1".to_string(),
+            "player1".to_string(),
+            None,
+        )));
+    }
+
+    #[test]
+    fn player_slide_hides_correct_answer() {
+        let template = test_template();
+        let current_slide = CurrentSlide {
+            starting_time: Instant::now(),
+            index: 1,
+            answered: false,
+        };
+        let player_slide = template.get_player_slide(current_slide);
+        assert_eq!(1, player_slide.slide_index);
+        assert_eq!(template.slides[1].answers, player_slide.answers);
+
+        let serialized = serde_json::to_string(&PlayerMessage::Slide {
+            slide: player_slide.clone(),
+        })
+        .unwrap();
+        assert!(!serialized.contains("correct_answer"));
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        assert!(player_slide.deadline > now);
+        assert!(player_slide.deadline <= now + 10_000);
+    }
+
+    #[test]
+    fn slide_validate_answer() {
+        let template = test_template();
+        let single = &template.slides[0];
+        let multiple = &template.slides[1];
+
+        assert_eq!(Ok(()), single.validate_answer(&[1]));
+        assert_eq!(Err(AnswerError::Empty), single.validate_answer(&[]));
+        assert_eq!(
+            Err(AnswerError::MultiplePicks),
+            single.validate_answer(&[0, 1])
+        );
+        assert_eq!(
+            Err(AnswerError::OutOfRange { index: 2 }),
+            single.validate_answer(&[2])
+        );
+
+        assert_eq!(Ok(()), multiple.validate_answer(&[0, 1, 3]));
+        assert_eq!(
+            Err(AnswerError::Duplicate { index: 0 }),
+            multiple.validate_answer(&[0, 3, 0])
+        );
+        assert_eq!(
+            Err(AnswerError::OutOfRange { index: 4 }),
+            multiple.validate_answer(&[0, 4])
+        );
+    }
+
+    #[test]
+    fn slide_grade() {
+        let template = test_template();
+        let single = &template.slides[0];
+        // Correct answers are 0 and 3
+        let multiple = &template.slides[1];
+
+        assert_eq!(1.0, single.grade(&[0], Grading::Exact));
+        assert_eq!(0.0, single.grade(&[1], Grading::Exact));
+        assert_eq!(1.0, single.grade(&[0], Grading::Partial));
+
+        assert_eq!(1.0, multiple.grade(&[3, 0], Grading::Exact));
+        assert_eq!(0.0, multiple.grade(&[0], Grading::Exact));
+        // Picking every option isn't correct
+        assert_eq!(0.0, multiple.grade(&[0, 1, 2, 3], Grading::Exact));
+
+        assert_eq!(0.5, multiple.grade(&[0], Grading::Partial));
+        assert_eq!(0.5, multiple.grade(&[0, 3, 1], Grading::Partial));
+        assert_eq!(0.0, multiple.grade(&[0, 1, 2, 3], Grading::Partial));
+        assert_eq!(0.0, multiple.grade(&[1, 2], Grading::Partial));
+    }
+
+    #[test]
+    fn template_validate() {
+        let template = test_template();
+        assert_eq!(Ok(()), template.validate());
+
+        let mut empty = test_template();
+        empty.slides.clear();
+        assert!(empty.validate().is_err());
+
+        let mut out_of_range = test_template();
+        out_of_range.slides[0].correct_answer = vec![2];
+        assert!(out_of_range.validate().is_err());
+
+        let mut several_correct = test_template();
+        several_correct.slides[0].correct_answer = vec![0, 1];
+        assert!(several_correct.validate().is_err());
+
+        let mut no_duration = test_template();
+        no_duration.slides[2].duration = 0;
+        assert!(no_duration.validate().is_err());
+    }
+
     const TEST_TEMPLATE: &str = r#"{
         "_id": "88109899-b13b-40a4-8fc4-e1d9986ade13",
     "name": "Test",
@@ -1,2 +1,8 @@
+pub mod config;
 pub mod game;
-pub mod actors;
\ No newline at end of file
+pub mod actors;
+pub mod metrics;
+pub mod scoring;
+pub mod sessions;
+pub mod storage;
+pub mod users;
@@ -7,72 +7,230 @@ use axum::{
     http::StatusCode,
     middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::get,
-    Extension, Router,
+    routing::{get, post},
+    Extension, Json, Router,
 };
 use axum_extra::{headers, TypedHeader};
 use futures_util::{sink::SinkExt, stream::StreamExt};
 use gudritis_axum::{
-    actors::{GameActorHandle, GameManagerActorHandle},
-    game::{Command, Player},
+    actors::{GameActorHandle, GameManagerActorHandle, HostError},
+    config::{Config, SessionBackend, StorageBackend},
+    game::{Command, GameMode, GameSettings, Grading, Player},
+    scoring::Scoring,
+    sessions::{guest_player_id, InMemorySessions, RedisSessions, SessionStore},
+    storage::{GameRepository, InMemoryRepository, MongoRepository, SqliteRepository},
+    users::validate_nickname,
 };
 use mongodb::{
     options::{ClientOptions, ServerApi, ServerApiVersion},
     Client as MongoDBClient, Database,
 };
-use redis::{self, Client as RedisClient, Commands};
-use serde::Deserialize;
-use std::{env, time::Instant};
+use serde::{Deserialize, Serialize};
+use std::{
+    process,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::sync::mpsc;
 
+/// How often an active websocket pushes back its login session's expiry.
+const SESSION_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
 #[derive(Clone)]
 struct AppState {
     game_manager: GameManagerActorHandle,
-    redis_client: RedisClient,
+    sessions: Arc<dyn SessionStore>,
+    repository: Arc<dyn GameRepository>,
+    heartbeat: Heartbeat,
+    /// How many messages can queue up for a client before it's considered too slow and disconnected.
+    client_buffer: usize,
+    /// Held by every open websocket, so shutdown can wait for them to send their last messages and close.
+    open_connection: mpsc::Sender<()>,
+}
+
+/// How often clients are pinged and how long they can stay silent before being marked as disconnected.
+#[derive(Clone, Copy)]
+struct Heartbeat {
+    interval: Duration,
+    idle_timeout: Duration,
 }
 
 #[derive(Clone)]
 struct PlayerWithGameHandle {
     player: Player,
     game_handle: GameActorHandle,
+    /// The login session the player joined with, kept alive while they're connected. Guests don't have one.
+    session_id: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct JoinQuery {
     code: usize,
+    /// Only used for guests, registered players get the nickname of their account.
+    #[serde(default)]
     nickname: String,
 }
 
+#[derive(Deserialize)]
+struct HostQuery {
+    id: String,
+    #[serde(default)]
+    auto_advance: bool,
+    #[serde(default)]
+    scoring: Scoring,
+    #[serde(default)]
+    grading: Grading,
+    #[serde(default)]
+    mode: GameMode,
+    #[serde(default)]
+    forbid_guests: bool,
+}
+
+#[derive(Serialize)]
+struct HostResponse {
+    code: u32,
+}
+
 #[tokio::main]
 async fn main() {
-    let db_addr = env::args()
-        .nth(2)
-        .unwrap_or_else(|| "mongodb://localhost:27017".to_string());
-    let redis_addr = env::args()
-        .nth(3)
-        .unwrap_or_else(|| "redis://127.0.0.1/".to_string());
-    let database = connect_db(db_addr).await.unwrap();
-    let redis_client = redis::Client::open(redis_addr).unwrap();
-    let game_manager = GameManagerActorHandle::new(database).await;
-
-    // App global state, used by the join_game middleware
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    };
+    // The level was checked when the config was loaded
+    tracing_subscriber::fmt()
+        .with_max_level(config.logging.level().unwrap_or(tracing::Level::INFO))
+        .init();
+
+    if let Err(err) = serve(config).await {
+        // The storage and session errors already include their source, so only the innermost reason is added
+        eprintln!("{err}: {}", err.root_cause());
+        process::exit(1);
+    }
+}
+
+/// Connects to the configured backends and serves games until the server is told to stop.
+///
+/// Fails if a backend can't be reached or the address can't be listened on.
+async fn serve(config: Config) -> Result<()> {
+    // Games can be kept in SQLite or in memory, optionally saved to a JSON file, to run without MongoDB
+    let storage = &config.storage;
+    let repository: Arc<dyn GameRepository> = match storage.store {
+        StorageBackend::Memory => match &storage.file {
+            Some(file) => Arc::new(
+                InMemoryRepository::open(file.clone())
+                    .with_context(|| format!("could not open game store {}", file.display()))?,
+            ),
+            None => Arc::new(InMemoryRepository::default()),
+        },
+        StorageBackend::Sqlite => {
+            let sqlite_file = storage.sqlite_file();
+            let repository = SqliteRepository::open(&sqlite_file).with_context(|| {
+                format!("could not open SQLite database {}", sqlite_file.display())
+            })?;
+            if let Some(file) = &storage.import_file {
+                let imported = repository
+                    .import(file.clone())
+                    .await
+                    .with_context(|| format!("could not import {}", file.display()))?;
+                tracing::info!("Imported {imported} game sessions from {}", file.display());
+            }
+            Arc::new(repository)
+        }
+        StorageBackend::Mongo => Arc::new(MongoRepository::new(
+            connect_db(storage.mongo_url.clone(), &storage.database)
+                .await
+                .context("could not connect to MongoDB")?,
+        )),
+    };
+    // Sessions can be kept in memory to run without Redis, though only guests can join then
+    let sessions: Arc<dyn SessionStore> = match config.sessions.store {
+        SessionBackend::Memory => Arc::new(InMemorySessions::default()),
+        SessionBackend::Redis => Arc::new(
+            RedisSessions::connect(&config.sessions.redis_url, config.timeouts.redis_command())
+                .await
+                .context("could not connect to Redis")?,
+        ),
+    };
+    let game_manager = GameManagerActorHandle::new(repository.clone(), config.limits).await;
+
+    let (open_connection, mut connections_closed) = mpsc::channel(1);
+
+    // App global state, used by the join_game middleware and the host route
     let state = AppState {
-        game_manager,
-        redis_client,
+        game_manager: game_manager.clone(),
+        sessions,
+        repository,
+        heartbeat: Heartbeat {
+            interval: config.timeouts.heartbeat_interval(),
+            idle_timeout: config.timeouts.idle_timeout(),
+        },
+        client_buffer: config.limits.client_buffer,
+        open_connection,
     };
 
+    // The host route is added after the join_game layer so it isn't wrapped by it
     let app = Router::new()
         .route("/ws", get(websocket_handler))
-        .layer(middleware::from_fn_with_state(state.clone(), join_game));
+        .layer(middleware::from_fn_with_state(state.clone(), join_game))
+        .route("/host", post(host_game))
+        .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
+    let listener = tokio::net::TcpListener::bind(config.server.bind)
         .await
-        .unwrap();
-    axum::serve(listener, app).await.unwrap();
+        .with_context(|| format!("could not listen on {}", config.server.bind))?;
+    tracing::info!("Listening on {}", config.server.bind);
+    let shutdown_deadline = config.timeouts.shutdown();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            tracing::info!("Shutting down");
+            // Saving games or closing connections can hang on a stuck backend or client, so don't wait on them forever
+            tokio::spawn(async move {
+                tokio::time::sleep(shutdown_deadline).await;
+                tracing::error!("Shutdown took longer than {shutdown_deadline:?}, exiting anyway");
+                process::exit(1);
+            });
+            game_manager.shutdown().await;
+        })
+        .await
+        .context("server error")?;
+    // The games have dropped their players, so this only waits for the websockets to flush what's left
+    let _ = connections_closed.recv().await;
+    Ok(())
+}
+
+/// Waits for SIGTERM, which deploys stop the server with, or Ctrl-C.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for Ctrl-C");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
 
 /// Connects to and returns a `Result` that contains a MongoDB database connection .
-pub async fn connect_db(db_addr: String) -> Result<Database, mongodb::error::Error> {
+pub async fn connect_db(
+    db_addr: String,
+    database: &str,
+) -> Result<Database, mongodb::error::Error> {
     let mut client_options = ClientOptions::parse(db_addr).await?;
 
     let server_api = ServerApi::builder().version(ServerApiVersion::V1).build(); //MongoDb Client
@@ -80,43 +238,140 @@ pub async fn connect_db(db_addr: String) -> Result<Database, mongodb::error::Err
 
     let client = MongoDBClient::with_options(client_options)?;
 
-    let game_db = client.database("Gudritis");
+    let game_db = client.database(database);
     Ok(game_db)
 }
 
+/// Hosts the game session with the id given in the query, responding with the code players join with.
+async fn host_game(
+    State(mut state): State<AppState>,
+    Query(host_query): Query<HostQuery>,
+) -> Response {
+    let settings = GameSettings {
+        auto_advance: host_query.auto_advance,
+        scoring: host_query.scoring,
+        grading: host_query.grading,
+        mode: host_query.mode,
+        forbid_guests: host_query.forbid_guests,
+    };
+    match state.game_manager.host_game(host_query.id, settings).await {
+        Ok(code) => (StatusCode::CREATED, Json(HostResponse { code })).into_response(),
+        Err(HostError::NotFound) => {
+            (StatusCode::NOT_FOUND, "No game session with that id").into_response()
+        }
+        Err(HostError::AlreadyHosted) => {
+            (StatusCode::CONFLICT, "Game session is already hosted").into_response()
+        }
+        Err(HostError::DuplicateCode(_)) => {
+            (StatusCode::CONFLICT, "Game code is already in use").into_response()
+        }
+        Err(HostError::InvalidTemplate(reason)) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Invalid game template: {reason}"),
+        )
+            .into_response(),
+        Err(HostError::ShuttingDown) => {
+            (StatusCode::SERVICE_UNAVAILABLE, "Server is shutting down").into_response()
+        }
+        Err(HostError::Unavailable) => {
+            tracing::error!("Failed to host game: the game manager has stopped");
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Games can't be hosted right now",
+            )
+                .into_response()
+        }
+        Err(err @ HostError::Storage(_)) => {
+            tracing::error!("Failed to host game: {:#}", err);
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Could not load the game session",
+            )
+                .into_response()
+        }
+    }
+}
+
 /// Middleware for extracting game join parameters and session cookie and attempting to connect to the game.
 ///
 /// Extends the request with a `PlayerWithGameHandle` if successful, otherwise returns appropriate `Response`.
 async fn join_game(
-    State(mut state): State<AppState>,
+    State(state): State<AppState>,
     Query(join_query): Query<JoinQuery>,
-    TypedHeader(cookies): TypedHeader<headers::Cookie>,
+    cookies: Option<TypedHeader<headers::Cookie>>,
     mut request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    // Gets the session cookie from the Cookie extractor
-    let session_cookie = match cookies.get("session") {
-        Some(cookie) => cookie,
-        None => return Ok((StatusCode::BAD_REQUEST, "No session cookie").into_response()),
+    if state.game_manager.is_shutting_down() {
+        return Ok((StatusCode::SERVICE_UNAVAILABLE, "Server is shutting down").into_response());
+    }
+    let cookie = |name| cookies.as_ref().and_then(|cookies| cookies.get(name));
+    // Logged in players are identified by their session, everyone else by the guest session the frontend hands out
+    let login = match cookie("session") {
+        Some(session_cookie) => state
+            .sessions
+            .player_id(session_cookie)
+            .await
+            .context("failed to look up login session")?
+            .map(|player_id| (session_cookie, player_id)),
+        None => None,
     };
-
-    // 
-    let player_id = state
-        .redis_client
-        .get(session_cookie)
-        .context("failed to retrieve session from redis")?;
-    let player = Player::new(player_id, join_query.nickname, None);
-    let game_handle = match state
-        .game_manager
-        .join_game(player.clone(), join_query.code as u32)
-        .await
-    {
+    // A player whose login expired can still play as a guest
+    let (player, session_id) = match (login, cookie("guest_session")) {
+        (Some((session_cookie, player_id)), _) => {
+            // Registered players always show up with their account's nickname
+            let nickname = match state
+                .repository
+                .user_nickname(&player_id)
+                .await
+                .context("failed to retrieve user from storage")?
+            {
+                Some(nickname) => nickname,
+                None => {
+                    return Ok(
+                        (StatusCode::UNAUTHORIZED, "Account not found, log in again")
+                            .into_response(),
+                    )
+                }
+            };
+            let player = Player::new(player_id, nickname, None);
+            (player, Some(session_cookie.to_string()))
+        }
+        (None, Some(guest_cookie)) => {
+            let player_id = match guest_player_id(guest_cookie) {
+                Some(player_id) => player_id,
+                None => {
+                    return Ok((StatusCode::UNAUTHORIZED, "Invalid guest session").into_response())
+                }
+            };
+            let nickname = match validate_nickname(&join_query.nickname) {
+                Ok(nickname) => nickname,
+                Err(err) => {
+                    return Ok(
+                        (StatusCode::BAD_REQUEST, format!("Invalid nickname: {err}"))
+                            .into_response(),
+                    )
+                }
+            };
+            (Player::guest(player_id, nickname, None), None)
+        }
+        (None, None) if cookie("session").is_some() => {
+            return Ok(
+                (StatusCode::UNAUTHORIZED, "Session has expired, log in again").into_response(),
+            );
+        }
+        (None, None) => {
+            return Ok((StatusCode::UNAUTHORIZED, "No session cookie").into_response());
+        }
+    };
+    let game_handle = match state.game_manager.find_game(join_query.code as u32) {
         Some(game_handle) => game_handle,
         None => return Ok((StatusCode::NOT_FOUND, "No game with that code").into_response()),
     };
     let player_with_game: PlayerWithGameHandle = PlayerWithGameHandle {
         player,
         game_handle,
+        session_id,
     };
 
     request.extensions_mut().insert(player_with_game);
@@ -127,90 +382,138 @@ async fn join_game(
 /// Attempts to upgrade the connection to websocket.
 async fn websocket_handler(
     ws: WebSocketUpgrade,
+    State(state): State<AppState>,
     Extension(player_with_game): Extension<PlayerWithGameHandle>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| websocket(socket, player_with_game))
+    ws.on_upgrade(move |socket| websocket(socket, player_with_game, state))
 }
 
 /// Handles the websocket connection and all valid messages sent by the player.
 ///
-/// Closes the connection in case an invalid message is sent, the player leaves the game or sends a Close message.
-async fn websocket(socket: WebSocket, player_with_game: PlayerWithGameHandle) {
+/// Closes the connection in case an invalid message is sent, the player leaves the game, sends a Close message
+/// or stays silent for longer than the heartbeat's idle timeout. The player is marked as having left in every case.
+async fn websocket(socket: WebSocket, player_with_game: PlayerWithGameHandle, state: AppState) {
     let (game, mut player) = (player_with_game.game_handle, player_with_game.player);
+    let (heartbeat, sessions, session_id) =
+        (state.heartbeat, state.sessions, player_with_game.session_id);
+    let _open_connection = state.open_connection;
     let (mut sender, mut receiver) = socket.split();
 
-    let (tx, mut rx) = mpsc::channel(8);
-    player.new_connection(tx);
-    game.join_game(player.clone()).await;
+    // The game is the only holder of the sender, so the send task ends when the game drops a client that fell behind
+    let (tx, mut rx) = mpsc::channel(state.client_buffer);
+    let player_id = player.get_id().to_string();
+    let connection_id = player.new_connection(tx);
+    if !game.join_game(player).await {
+        // The game already told the player why, so pass that on before closing
+        while let Ok(payload) = rx.try_recv() {
+            let _ = sender.send(payload.into()).await;
+        }
+        return;
+    }
 
     let mut send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
+        let mut ping_interval = tokio::time::interval(heartbeat.interval);
+        loop {
+            let msg = tokio::select! {
+                payload = rx.recv() => match payload {
+                    Some(payload) => payload.into(),
+                    None => break,
+                },
+                _ = ping_interval.tick() => Message::Ping(vec![]),
+            };
             if sender.send(msg).await.is_err() {
                 break;
             }
         }
     });
 
-    let mut recv_task = tokio::spawn(async move {
-        while let Some(Ok(msg)) = receiver.next().await {
-            let player_id = player.get_id();
-            match msg {
-                Message::Text(msg) => {
-                    let json = match serde_json::from_str(&msg) {
-                        Ok(json) => json,
-                        Err(_) => break,
-                    };
-                    let command = match Command::from_json(json) {
-                        Ok(command) => command,
-                        Err(_) => break,
-                    };
-                    match command {
-                        Command::Start => {
-                            game.start_game(player_id.to_string()).await;
-                        }
-                        Command::Answer {
-                            answer,
-                            slide_index,
-                        } => {
-                            let answer_submit_time = Instant::now();
-                            game.submit_answer(
-                                slide_index,
+    let mut recv_task = tokio::spawn({
+        let game = game.clone();
+        let player_id = player_id.clone();
+        async move {
+            let mut last_refresh: Option<Instant> = None;
+            // Any frame counts as activity, so clients answering the pings stay connected
+            while let Ok(Some(Ok(msg))) =
+                tokio::time::timeout(heartbeat.idle_timeout, receiver.next()).await
+            {
+                let refresh_due = last_refresh
+                    .is_none_or(|last_refresh| last_refresh.elapsed() >= SESSION_REFRESH_INTERVAL);
+                if let (true, Some(session_id)) = (refresh_due, &session_id) {
+                    last_refresh = Some(Instant::now());
+                    tokio::spawn(refresh_session(sessions.clone(), session_id.clone()));
+                }
+                match msg {
+                    Message::Text(msg) => {
+                        let json = match serde_json::from_str(&msg) {
+                            Ok(json) => json,
+                            Err(_) => break,
+                        };
+                        let command = match Command::from_json(json) {
+                            Ok(command) => command,
+                            Err(_) => break,
+                        };
+                        match command {
+                            Command::Start => {
+                                game.start_game(player_id.to_string()).await;
+                            }
+                            Command::Answer {
                                 answer,
-                                answer_submit_time,
-                                player_id.to_string(),
-                            )
-                            .await;
-                        }
-                        Command::Leave => {
-                            game.leave(player_id.to_string()).await;
-                            break;
-                        }
-                        Command::End => {
-                            game.end(player_id.to_string()).await;
-                        }
-                        Command::Kick {
-                            player_id: kick_player_id,
-                        } => {
-                            game.kick(kick_player_id, player_id.to_string()).await;
+                                slide_index,
+                            } => {
+                                let answer_submit_time = Instant::now();
+                                game.submit_answer(
+                                    slide_index,
+                                    answer,
+                                    answer_submit_time,
+                                    player_id.to_string(),
+                                )
+                                .await;
+                            }
+                            Command::Next => {
+                                game.next_slide(player_id.to_string()).await;
+                            }
+                            Command::Close => {
+                                game.close_answers(player_id.to_string()).await;
+                            }
+                            Command::Leave => {
+                                break;
+                            }
+                            Command::End => {
+                                game.end(player_id.to_string()).await;
+                            }
+                            Command::Kick {
+                                player_id: kick_player_id,
+                            } => {
+                                game.kick(kick_player_id, player_id.to_string()).await;
+                            }
+                            _ => {}
                         }
-                        _ => {}
                     }
-                }
-                Message::Close(_) => {
-                    game.leave(player_id.to_string()).await;
-                    break;
-                }
-                _ => {
-                    break;
+                    // Pings are answered by the websocket itself
+                    Message::Ping(_) | Message::Pong(_) | Message::Binary(_) => {}
+                    Message::Close(_) => {
+                        break;
+                    }
                 }
             }
+            // A half-open socket can time out long after the player reconnected, which the game then ignores
+            game.leave(player_id.to_string(), connection_id).await;
         }
     });
 
     tokio::select! {
         _ = (&mut recv_task) => send_task.abort(),
-        _ = (&mut send_task) => recv_task.abort(),
+        _ = (&mut send_task) => {
+            recv_task.abort();
+            game.leave(player_id, connection_id).await;
+        }
+    }
+}
 
+/// Keeps the player's login session from expiring while they're playing.
+async fn refresh_session(sessions: Arc<dyn SessionStore>, session_id: String) {
+    if let Err(err) = sessions.refresh(&session_id).await {
+        tracing::warn!("Failed to refresh session: {:#}", err);
     }
 }
 
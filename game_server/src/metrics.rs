@@ -0,0 +1,25 @@
+//! Process wide counters for monitoring the server.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static DROPPED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+static SLOW_CONSUMERS: AtomicU64 = AtomicU64::new(0);
+
+/// Number of messages that were dropped because the receiving client couldn't keep up, counting both the message
+/// that found its queue full and every message the client missed until it reconnected.
+pub fn dropped_messages() -> u64 {
+    DROPPED_MESSAGES.load(Ordering::Relaxed)
+}
+
+/// Number of clients that were disconnected for falling behind on their messages.
+pub fn slow_consumers() -> u64 {
+    SLOW_CONSUMERS.load(Ordering::Relaxed)
+}
+
+pub(crate) fn record_slow_consumer() {
+    SLOW_CONSUMERS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_dropped_message() {
+    DROPPED_MESSAGES.fetch_add(1, Ordering::Relaxed);
+}
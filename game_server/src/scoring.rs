@@ -0,0 +1,208 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Everything a `ScoringPolicy` gets to know about a single answer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoredAnswer {
+    /// Whether the player submitted anything before the slide ran out of time.
+    pub answered: bool,
+    /// Whether the answer earned full credit.
+    pub correct: bool,
+    /// Share of the slide's points the answer earned, between 0 and 1. Below 1 only with partial credit grading.
+    pub credit: f64,
+    /// Time between the slide being sent and the answer being submitted.
+    pub elapsed: Duration,
+    /// How long the slide was open for answers.
+    pub duration: Duration,
+    /// Number of correct answers the player gave in a row before this one.
+    pub streak: u32,
+}
+
+/// Decides how many points an answer is worth. Points can be negative for policies that penalize wrong answers.
+pub trait ScoringPolicy: Send + Sync {
+    fn score(&self, answer: &ScoredAnswer) -> i32;
+}
+
+/// Scales points by the credit the answer earned.
+fn with_credit(points: i32, answer: &ScoredAnswer) -> i32 {
+    (points as f64 * answer.credit).round() as i32
+}
+
+/// The same amount of points for every correct answer.
+pub struct Flat {
+    pub points: i32,
+}
+
+impl ScoringPolicy for Flat {
+    fn score(&self, answer: &ScoredAnswer) -> i32 {
+        with_credit(self.points, answer)
+    }
+}
+
+/// Correct answers are worth `max_points` when given instantly, dropping linearly to `min_points` at the deadline.
+pub struct TimeDecay {
+    pub max_points: i32,
+    pub min_points: i32,
+}
+
+impl ScoringPolicy for TimeDecay {
+    fn score(&self, answer: &ScoredAnswer) -> i32 {
+        if answer.credit <= 0.0 {
+            return 0;
+        }
+        if answer.duration.is_zero() {
+            return with_credit(self.max_points, answer);
+        }
+        let remaining = 1.0 - (answer.elapsed.as_secs_f64() / answer.duration.as_secs_f64());
+        let remaining = remaining.clamp(0.0, 1.0);
+        let points = self.min_points
+            + ((self.max_points - self.min_points) as f64 * remaining).round() as i32;
+        with_credit(points, answer)
+    }
+}
+
+/// Multiplies the points of another policy by how many correct answers the player has given in a row.
+///
+/// Each answer in the streak adds `step` to the multiplier, up to `max_multiplier`.
+pub struct StreakMultiplier<P> {
+    pub policy: P,
+    pub step: f64,
+    pub max_multiplier: f64,
+}
+
+impl<P: ScoringPolicy> ScoringPolicy for StreakMultiplier<P> {
+    fn score(&self, answer: &ScoredAnswer) -> i32 {
+        let points = self.policy.score(answer);
+        let multiplier = (1.0 + self.step * answer.streak as f64).min(self.max_multiplier);
+        (points as f64 * multiplier).round() as i32
+    }
+}
+
+/// Correct answers earn `points`, answers without any credit lose `penalty`. Running out of time costs nothing.
+pub struct NegativeMarking {
+    pub points: i32,
+    pub penalty: i32,
+}
+
+impl ScoringPolicy for NegativeMarking {
+    fn score(&self, answer: &ScoredAnswer) -> i32 {
+        if answer.credit > 0.0 {
+            with_credit(self.points, answer)
+        } else if answer.answered {
+            -self.penalty
+        } else {
+            0
+        }
+    }
+}
+
+/// The scoring policies a host can pick from when hosting a game.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scoring {
+    #[default]
+    Flat,
+    TimeDecay,
+    Streak,
+    NegativeMarking,
+}
+
+impl Scoring {
+    /// Builds the policy with the game's default point values.
+    pub fn policy(&self) -> Box<dyn ScoringPolicy> {
+        match self {
+            Scoring::Flat => Box::new(Flat { points: 1000 }),
+            Scoring::TimeDecay => Box::new(TimeDecay {
+                max_points: 1000,
+                min_points: 500,
+            }),
+            Scoring::Streak => Box::new(StreakMultiplier {
+                policy: TimeDecay {
+                    max_points: 1000,
+                    min_points: 500,
+                },
+                step: 0.1,
+                max_multiplier: 1.5,
+            }),
+            Scoring::NegativeMarking => Box::new(NegativeMarking {
+                points: 1000,
+                penalty: 500,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(correct: bool, elapsed_secs: f64) -> ScoredAnswer {
+        ScoredAnswer {
+            answered: true,
+            correct,
+            credit: if correct { 1.0 } else { 0.0 },
+            elapsed: Duration::from_secs_f64(elapsed_secs),
+            duration: Duration::from_secs(10),
+            streak: 0,
+        }
+    }
+
+    #[test]
+    fn flat() {
+        let policy = Flat { points: 100 };
+        assert_eq!(100, policy.score(&answer(true, 1.0)));
+        assert_eq!(100, policy.score(&answer(true, 9.0)));
+        assert_eq!(0, policy.score(&answer(false, 1.0)));
+
+        let mut partial = answer(false, 1.0);
+        partial.credit = 0.5;
+        assert_eq!(50, policy.score(&partial));
+    }
+
+    #[test]
+    fn time_decay() {
+        let policy = TimeDecay {
+            max_points: 1000,
+            min_points: 500,
+        };
+        assert_eq!(1000, policy.score(&answer(true, 0.0)));
+        assert_eq!(750, policy.score(&answer(true, 5.0)));
+        assert_eq!(500, policy.score(&answer(true, 10.0)));
+        assert_eq!(500, policy.score(&answer(true, 12.0)));
+        assert_eq!(0, policy.score(&answer(false, 0.0)));
+    }
+
+    #[test]
+    fn streak_multiplier() {
+        let policy = StreakMultiplier {
+            policy: Flat { points: 100 },
+            step: 0.1,
+            max_multiplier: 1.5,
+        };
+        let mut streak_answer = answer(true, 1.0);
+        assert_eq!(100, policy.score(&streak_answer));
+        streak_answer.streak = 2;
+        assert_eq!(120, policy.score(&streak_answer));
+        streak_answer.streak = 20;
+        assert_eq!(150, policy.score(&streak_answer));
+        streak_answer.correct = false;
+        streak_answer.credit = 0.0;
+        assert_eq!(0, policy.score(&streak_answer));
+    }
+
+    #[test]
+    fn negative_marking() {
+        let policy = NegativeMarking {
+            points: 100,
+            penalty: 50,
+        };
+        assert_eq!(100, policy.score(&answer(true, 1.0)));
+        assert_eq!(-50, policy.score(&answer(false, 1.0)));
+        let mut partial = answer(false, 1.0);
+        partial.credit = 0.5;
+        assert_eq!(50, policy.score(&partial));
+        let mut timed_out = answer(false, 10.0);
+        timed_out.answered = false;
+        assert_eq!(0, policy.score(&timed_out));
+    }
+}
@@ -0,0 +1,221 @@
+//! Looks up which player a login session cookie belongs to.
+
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, AsyncCommands, RedisError};
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// How long a session lives without activity. Matches the expiry the frontend sets on the cookie.
+pub const SESSION_TTL: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// Turns the UUID from a `guest_session` cookie into the guest's player id.
+///
+/// Guest ids are prefixed so they can never collide with the ids of registered users. Returns `None` if the cookie
+/// isn't a valid UUID.
+pub fn guest_player_id(guest_session: &str) -> Option<String> {
+    let uuid = Uuid::parse_str(guest_session).ok()?;
+    Some(format!("guest:{}", uuid.hyphenated()))
+}
+
+/// Reasons a session could not be looked up.
+#[derive(Debug)]
+pub enum SessionError {
+    /// Redis didn't answer in time.
+    Timeout,
+    /// Redis answered with an error or the connection failed.
+    Redis(RedisError),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Timeout => write!(f, "session store timed out"),
+            SessionError::Redis(err) => write!(f, "session store error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SessionError::Redis(err) => Some(err),
+            SessionError::Timeout => None,
+        }
+    }
+}
+
+/// Where login sessions are kept, mapping session ids to the ids of the players they belong to.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Returns the id of the player the session belongs to, or `None` if it's missing or has expired.
+    async fn player_id(&self, session_id: &str) -> Result<Option<String>, SessionError>;
+
+    /// Pushes the session's expiry back to a full `SESSION_TTL` from now.
+    async fn refresh(&self, session_id: &str) -> Result<(), SessionError>;
+
+    /// Ends the session so it can no longer be used to join games.
+    async fn revoke(&self, session_id: &str) -> Result<(), SessionError>;
+}
+
+/// Sessions stored in Redis as `session id -> player id`, shared by the frontend that logs players in.
+///
+/// Uses a single multiplexed connection that reconnects on its own, so it's cheap to clone into every request.
+#[derive(Clone)]
+pub struct RedisSessions {
+    connection: ConnectionManager,
+    /// How long a single Redis command may take before giving up on it.
+    command_timeout: Duration,
+}
+
+impl RedisSessions {
+    pub async fn connect(redis_url: &str, command_timeout: Duration) -> Result<Self, SessionError> {
+        let client = redis::Client::open(redis_url).map_err(SessionError::Redis)?;
+        let connection = with_timeout(command_timeout, ConnectionManager::new(client)).await?;
+        Ok(RedisSessions {
+            connection,
+            command_timeout,
+        })
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedisSessions {
+    async fn player_id(&self, session_id: &str) -> Result<Option<String>, SessionError> {
+        let mut connection = self.connection.clone();
+        with_timeout(self.command_timeout, connection.get(session_id)).await
+    }
+
+    async fn refresh(&self, session_id: &str) -> Result<(), SessionError> {
+        let mut connection = self.connection.clone();
+        with_timeout(
+            self.command_timeout,
+            connection.expire(session_id, SESSION_TTL.as_secs() as i64),
+        )
+        .await
+    }
+
+    async fn revoke(&self, session_id: &str) -> Result<(), SessionError> {
+        let mut connection = self.connection.clone();
+        with_timeout(self.command_timeout, connection.del(session_id)).await
+    }
+}
+
+async fn with_timeout<T>(
+    timeout: Duration,
+    command: impl Future<Output = Result<T, RedisError>>,
+) -> Result<T, SessionError> {
+    match tokio::time::timeout(timeout, command).await {
+        Ok(result) => result.map_err(SessionError::Redis),
+        Err(_) => Err(SessionError::Timeout),
+    }
+}
+
+/// Sessions kept in the server's memory, for running without Redis. They're lost when the server stops.
+pub struct InMemorySessions {
+    ttl: Duration,
+    /// Session id to player id and when the session expires.
+    sessions: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl InMemorySessions {
+    pub fn new(ttl: Duration) -> Self {
+        InMemorySessions {
+            ttl,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Logs the player in with the given session id.
+    pub fn insert(&self, session_id: String, player_id: String) {
+        let expires = Instant::now() + self.ttl;
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session_id, (player_id, expires));
+    }
+}
+
+impl Default for InMemorySessions {
+    fn default() -> Self {
+        InMemorySessions::new(SESSION_TTL)
+    }
+}
+
+#[async_trait]
+impl SessionStore for InMemorySessions {
+    async fn player_id(&self, session_id: &str) -> Result<Option<String>, SessionError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(session_id) {
+            Some((player_id, expires)) if *expires > Instant::now() => Ok(Some(player_id.clone())),
+            Some(_) => {
+                sessions.remove(session_id);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn refresh(&self, session_id: &str) -> Result<(), SessionError> {
+        if let Some((_, expires)) = self.sessions.lock().unwrap().get_mut(session_id) {
+            *expires = Instant::now() + self.ttl;
+        }
+        Ok(())
+    }
+
+    async fn revoke(&self, session_id: &str) -> Result<(), SessionError> {
+        self.sessions.lock().unwrap().remove(session_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guest_ids() {
+        assert_eq!(
+            Some("guest:6fe6bc9d-89f5-4993-b2e8-8e18a7fc9037".to_string()),
+            guest_player_id("6FE6BC9D-89F5-4993-B2E8-8E18A7FC9037")
+        );
+        assert_eq!(None, guest_player_id("6fe6bc9d"));
+        assert_eq!(
+            None,
+            guest_player_id("guest:6fe6bc9d-89f5-4993-b2e8-8e18a7fc9037")
+        );
+    }
+
+    #[tokio::test]
+    async fn in_memory_sessions() {
+        let sessions = InMemorySessions::default();
+        sessions.insert("session".to_string(), "player1".to_string());
+        assert_eq!(
+            Some("player1".to_string()),
+            sessions.player_id("session").await.unwrap()
+        );
+        assert_eq!(None, sessions.player_id("other").await.unwrap());
+
+        sessions.revoke("session").await.unwrap();
+        assert_eq!(None, sessions.player_id("session").await.unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn in_memory_sessions_expire() {
+        let sessions = InMemorySessions::new(Duration::from_millis(200));
+        sessions.insert("session".to_string(), "player1".to_string());
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        sessions.refresh("session").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert!(sessions.player_id("session").await.unwrap().is_some());
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(None, sessions.player_id("session").await.unwrap());
+    }
+}
@@ -0,0 +1,509 @@
+//! Where game sessions are loaded from and their results are saved to.
+
+use crate::game::{StoredGameSession, StoredPlayer, StoredPlayerAnswers};
+use async_trait::async_trait;
+use mongodb::{
+    bson::{doc, to_bson},
+    options::FindOneOptions,
+    Database,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, io, path::PathBuf, sync::Mutex};
+
+mod sqlite;
+
+pub use sqlite::SqliteRepository;
+
+/// Reasons the storage backend could not be read or written.
+#[derive(Debug)]
+pub enum StorageError {
+    Mongo(mongodb::error::Error),
+    Bson(mongodb::bson::ser::Error),
+    Sqlite(rusqlite::Error),
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Mongo(err) => write!(f, "database error: {err}"),
+            StorageError::Bson(err) => write!(f, "failed to serialize document: {err}"),
+            StorageError::Sqlite(err) => write!(f, "SQLite error: {err}"),
+            StorageError::Io(err) => write!(f, "failed to access storage file: {err}"),
+            StorageError::Json(err) => write!(f, "invalid storage file: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StorageError::Mongo(err) => Some(err),
+            StorageError::Bson(err) => Some(err),
+            StorageError::Sqlite(err) => Some(err),
+            StorageError::Io(err) => Some(err),
+            StorageError::Json(err) => Some(err),
+        }
+    }
+}
+
+/// Everything the server needs to persist.
+///
+/// Game sessions are created by the frontend, the server loads them when they're hosted and writes back the results
+/// once they end. Registered users' nicknames are looked up here too, since they live alongside the sessions.
+#[async_trait]
+pub trait GameRepository: Send + Sync {
+    /// Loads a game session, or `None` if there isn't one with the given id.
+    async fn load_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<StoredGameSession>, StorageError>;
+
+    /// Replaces the session's players and answers with the ones from the game.
+    async fn save_results(
+        &self,
+        session_id: &str,
+        players: &[StoredPlayer],
+        player_answers: &[StoredPlayerAnswers],
+    ) -> Result<(), StorageError>;
+
+    /// Marks the session as no longer running.
+    async fn mark_inactive(&self, session_id: &str) -> Result<(), StorageError>;
+
+    /// Looks up the nickname a registered user picked for their account. Returns `None` if the user doesn't exist.
+    async fn user_nickname(&self, user_id: &str) -> Result<Option<String>, StorageError>;
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct StoredUser {
+    _id: String,
+    nickname: String,
+}
+
+/// The MongoDB database shared with the frontend.
+pub struct MongoRepository {
+    db: Database,
+}
+
+impl MongoRepository {
+    pub fn new(db: Database) -> Self {
+        MongoRepository { db }
+    }
+}
+
+#[async_trait]
+impl GameRepository for MongoRepository {
+    async fn load_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<StoredGameSession>, StorageError> {
+        self.db
+            .collection::<StoredGameSession>("gameSessions")
+            .find_one(doc! {"_id": session_id}, None)
+            .await
+            .map_err(StorageError::Mongo)
+    }
+
+    async fn save_results(
+        &self,
+        session_id: &str,
+        players: &[StoredPlayer],
+        player_answers: &[StoredPlayerAnswers],
+    ) -> Result<(), StorageError> {
+        let update = doc! {
+            "$set": {
+                "players": to_bson(players).map_err(StorageError::Bson)?,
+                "player_answers": to_bson(player_answers).map_err(StorageError::Bson)?,
+            }
+        };
+        self.db
+            .collection::<StoredGameSession>("gameSessions")
+            .update_one(doc! {"_id": session_id}, update, None)
+            .await
+            .map_err(StorageError::Mongo)?;
+        Ok(())
+    }
+
+    async fn mark_inactive(&self, session_id: &str) -> Result<(), StorageError> {
+        self.db
+            .collection::<StoredGameSession>("gameSessions")
+            .update_one(
+                doc! {"_id": session_id},
+                doc! {"$set": {"active": false}},
+                None,
+            )
+            .await
+            .map_err(StorageError::Mongo)?;
+        Ok(())
+    }
+
+    async fn user_nickname(&self, user_id: &str) -> Result<Option<String>, StorageError> {
+        let options = FindOneOptions::builder()
+            .projection(doc! {"_id": 1, "nickname": 1})
+            .build();
+        let user = self
+            .db
+            .collection::<StoredUser>("users")
+            .find_one(doc! {"_id": user_id}, options)
+            .await
+            .map_err(StorageError::Mongo)?;
+        Ok(user.map(|user| user.nickname))
+    }
+}
+
+/// The contents of a JSON storage file.
+#[derive(Default, Deserialize, Serialize)]
+struct StoredData {
+    #[serde(default)]
+    game_sessions: Vec<StoredGameSession>,
+    #[serde(default)]
+    users: Vec<StoredUser>,
+}
+
+/// Keeps everything in memory, optionally backed by a JSON file, so the server can run without a database.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    sessions: Mutex<HashMap<String, StoredGameSession>>,
+    users: Mutex<HashMap<String, String>>,
+    /// Rewritten with the current contents whenever something changes.
+    file: Option<PathBuf>,
+    /// Held from taking a snapshot until it's written, so concurrent saves can't land out of order or interleave.
+    file_lock: tokio::sync::Mutex<()>,
+}
+
+impl InMemoryRepository {
+    /// Loads the sessions and users from a JSON file, starting out empty if it doesn't exist yet.
+    pub fn open(file: PathBuf) -> Result<Self, StorageError> {
+        let data: StoredData = match std::fs::read_to_string(&file) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(StorageError::Json)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => StoredData::default(),
+            Err(err) => return Err(StorageError::Io(err)),
+        };
+        let repository = InMemoryRepository {
+            file: Some(file),
+            ..InMemoryRepository::default()
+        };
+        for session in data.game_sessions {
+            repository.insert_session(session);
+        }
+        for user in data.users {
+            repository.insert_user(user._id, user.nickname);
+        }
+        Ok(repository)
+    }
+
+    pub fn insert_session(&self, session: StoredGameSession) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session.get_id().to_string(), session);
+    }
+
+    pub fn insert_user(&self, user_id: String, nickname: String) {
+        self.users.lock().unwrap().insert(user_id, nickname);
+    }
+
+    /// Changes a stored session and writes the result to the file, if there is one.
+    async fn update_session(
+        &self,
+        session_id: &str,
+        update: impl FnOnce(&mut StoredGameSession),
+    ) -> Result<(), StorageError> {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(session_id) {
+            update(session);
+        }
+        self.save().await
+    }
+
+    async fn save(&self) -> Result<(), StorageError> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let _file_lock = self.file_lock.lock().await;
+        let data = StoredData {
+            game_sessions: self.sessions.lock().unwrap().values().cloned().collect(),
+            users: self
+                .users
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(user_id, nickname)| StoredUser {
+                    _id: user_id.clone(),
+                    nickname: nickname.clone(),
+                })
+                .collect(),
+        };
+        let contents = serde_json::to_string_pretty(&data).map_err(StorageError::Json)?;
+        // Written next to the file and moved over it, so a crash mid-write can't leave a truncated file behind
+        let mut temp_file = file.clone().into_os_string();
+        temp_file.push(".tmp");
+        tokio::fs::write(&temp_file, contents)
+            .await
+            .map_err(StorageError::Io)?;
+        tokio::fs::rename(&temp_file, file)
+            .await
+            .map_err(StorageError::Io)
+    }
+}
+
+#[async_trait]
+impl GameRepository for InMemoryRepository {
+    async fn load_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<StoredGameSession>, StorageError> {
+        Ok(self.sessions.lock().unwrap().get(session_id).cloned())
+    }
+
+    async fn save_results(
+        &self,
+        session_id: &str,
+        players: &[StoredPlayer],
+        player_answers: &[StoredPlayerAnswers],
+    ) -> Result<(), StorageError> {
+        self.update_session(session_id, |session| {
+            session.set_results(players.to_vec(), player_answers.to_vec())
+        })
+        .await
+    }
+
+    async fn mark_inactive(&self, session_id: &str) -> Result<(), StorageError> {
+        self.update_session(session_id, StoredGameSession::deactivate)
+            .await
+    }
+
+    async fn user_nickname(&self, user_id: &str) -> Result<Option<String>, StorageError> {
+        Ok(self.users.lock().unwrap().get(user_id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn stored_player() -> StoredPlayer {
+        serde_json::from_value(json!({
+            "registered_player": true,
+            "player_id": "player1",
+            "nickname": "player1",
+            "score": 1000,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn in_memory_repository() {
+        let repository = InMemoryRepository::default();
+        repository.insert_session(StoredGameSession::fixture());
+        repository.insert_user("player1".to_string(), "Player One".to_string());
+
+        assert!(repository.load_session("other").await.unwrap().is_none());
+        assert_eq!(
+            Some("Player One".to_string()),
+            repository.user_nickname("player1").await.unwrap()
+        );
+        assert_eq!(None, repository.user_nickname("other").await.unwrap());
+
+        repository
+            .save_results("session", &[stored_player()], &[])
+            .await
+            .unwrap();
+        repository.mark_inactive("session").await.unwrap();
+        let session = repository.load_session("session").await.unwrap().unwrap();
+        let session = serde_json::to_value(session).unwrap();
+        assert_eq!(json!(false), session["active"]);
+        assert_eq!(json!("player1"), session["players"][0]["player_id"]);
+    }
+
+    #[tokio::test]
+    async fn json_file_repository() {
+        let file =
+            std::env::temp_dir().join(format!("gudritis-storage-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&file);
+
+        let repository = InMemoryRepository::open(file.clone()).unwrap();
+        repository.insert_session(StoredGameSession::fixture());
+        repository
+            .save_results("session", &[stored_player()], &[])
+            .await
+            .unwrap();
+
+        // Everything survives a restart
+        let repository = InMemoryRepository::open(file.clone()).unwrap();
+        let session = repository.load_session("session").await.unwrap().unwrap();
+        let session = serde_json::to_value(session).unwrap();
+        assert_eq!(json!(1000), session["players"][0]["score"]);
+        assert_eq!(json!(true), session["active"]);
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn json_file_concurrent_saves() {
+        let file = std::env::temp_dir().join(format!(
+            "gudritis-storage-concurrent-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&file);
+
+        let repository = std::sync::Arc::new(InMemoryRepository::open(file.clone()).unwrap());
+        for index in 0..20 {
+            let mut session = serde_json::to_value(StoredGameSession::fixture()).unwrap();
+            session["_id"] = json!(format!("session{index}"));
+            repository.insert_session(serde_json::from_value(session).unwrap());
+        }
+        // Every game saving at once, as they do when the server shuts down
+        let saves: Vec<_> = (0..20)
+            .map(|index| {
+                let repository = repository.clone();
+                tokio::spawn(async move {
+                    repository
+                        .save_results(&format!("session{index}"), &[stored_player()], &[])
+                        .await
+                })
+            })
+            .collect();
+        for save in saves {
+            save.await.unwrap().unwrap();
+        }
+
+        // No save got overwritten by an older snapshot
+        let repository = InMemoryRepository::open(file.clone()).unwrap();
+        for index in 0..20 {
+            let session = repository
+                .load_session(&format!("session{index}"))
+                .await
+                .unwrap()
+                .unwrap();
+            let session = serde_json::to_value(session).unwrap();
+            assert_eq!(json!("player1"), session["players"][0]["player_id"]);
+        }
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    fn stored_answer() -> StoredPlayerAnswers {
+        serde_json::from_value(json!({
+            "player_id": "player1",
+            "question_index": 1,
+            "answers": [0, 3],
+            "time": 1500,
+            "correct": true,
+            "points": 1000,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sqlite_repository() {
+        let repository = SqliteRepository::in_memory().unwrap();
+        let mut session = StoredGameSession::fixture();
+        session.set_results(vec![stored_player()], vec![]);
+        repository.insert_session(session).await.unwrap();
+        repository
+            .insert_user("player1".to_string(), "Player One".to_string())
+            .await
+            .unwrap();
+
+        assert!(repository.load_session("other").await.unwrap().is_none());
+        assert_eq!(
+            Some("Player One".to_string()),
+            repository.user_nickname("player1").await.unwrap()
+        );
+        assert_eq!(None, repository.user_nickname("other").await.unwrap());
+
+        let loaded = repository.load_session("session").await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(StoredGameSession::fixture()).unwrap()["template"],
+            serde_json::to_value(&loaded).unwrap()["template"]
+        );
+
+        repository
+            .save_results("session", &[stored_player()], &[stored_answer()])
+            .await
+            .unwrap();
+        repository.mark_inactive("session").await.unwrap();
+        // Results for a session that isn't stored are ignored
+        repository
+            .save_results("other", &[stored_player()], &[])
+            .await
+            .unwrap();
+
+        let session = repository.load_session("session").await.unwrap().unwrap();
+        let session = serde_json::to_value(session).unwrap();
+        assert_eq!(json!(false), session["active"]);
+        assert_eq!(1, session["players"].as_array().unwrap().len());
+        assert_eq!(json!(1000), session["players"][0]["score"]);
+        assert_eq!(
+            serde_json::to_value(stored_answer()).unwrap(),
+            session["player_answers"][0]
+        );
+    }
+
+    #[tokio::test]
+    async fn sqlite_sessions_keep_their_template() {
+        let repository = SqliteRepository::in_memory().unwrap();
+        repository
+            .insert_session(StoredGameSession::fixture())
+            .await
+            .unwrap();
+        // A later session of the same template, after it was edited
+        let mut session = serde_json::to_value(StoredGameSession::fixture()).unwrap();
+        session["_id"] = json!("session2");
+        session["template"]["name"] = json!("Edited");
+        repository
+            .insert_session(serde_json::from_value(session).unwrap())
+            .await
+            .unwrap();
+
+        let first = repository.load_session("session").await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(StoredGameSession::fixture()).unwrap()["template"],
+            serde_json::to_value(first).unwrap()["template"]
+        );
+        let second = repository.load_session("session2").await.unwrap().unwrap();
+        assert_eq!(
+            json!("Edited"),
+            serde_json::to_value(second).unwrap()["template"]["name"]
+        );
+    }
+
+    #[tokio::test]
+    async fn sqlite_import() {
+        let file =
+            std::env::temp_dir().join(format!("gudritis-import-{}.json", std::process::id()));
+        let data = json!({
+            "game_sessions": [StoredGameSession::fixture()],
+            "users": [{ "_id": "player1", "nickname": "Player One" }],
+        });
+        std::fs::write(&file, data.to_string()).unwrap();
+
+        let repository = SqliteRepository::in_memory().unwrap();
+        assert_eq!(1, repository.import(file.clone()).await.unwrap());
+        assert_eq!(
+            Some("Player One".to_string()),
+            repository.user_nickname("player1").await.unwrap()
+        );
+        repository
+            .save_results("session", &[stored_player()], &[])
+            .await
+            .unwrap();
+
+        // Importing the same file again on the next startup keeps the results
+        assert_eq!(0, repository.import(file.clone()).await.unwrap());
+        let session = repository.load_session("session").await.unwrap().unwrap();
+        let session = serde_json::to_value(session).unwrap();
+        assert_eq!(json!("player1"), session["players"][0]["player_id"]);
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn sqlite_migrations() {
+        let file = std::env::temp_dir().join(format!("gudritis-storage-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&file);
+        SqliteRepository::open(&file).unwrap();
+        // Opening an up to date database doesn't apply the migrations again
+        SqliteRepository::open(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,407 @@
+//! Game sessions stored in an embedded SQLite database, for deployments without MongoDB.
+
+use super::{GameRepository, StorageError, StoredData};
+use crate::game::{StoredGameSession, StoredGameTemplate, StoredPlayer, StoredPlayerAnswers};
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Schema changes, applied in order. The database's `user_version` is the number of them already applied.
+const MIGRATIONS: &[&str] = &[r#"
+    CREATE TABLE game_sessions (
+        id TEXT PRIMARY KEY,
+        code INTEGER NOT NULL,
+        active INTEGER NOT NULL,
+        host TEXT NOT NULL,
+        template_id TEXT NOT NULL,
+        -- The whole template document, slides included. Every session keeps its own copy, as templates can be
+        -- edited after a session is played
+        template TEXT NOT NULL
+    );
+    CREATE TABLE session_players (
+        session_id TEXT NOT NULL REFERENCES game_sessions (id),
+        player_id TEXT NOT NULL,
+        nickname TEXT NOT NULL,
+        registered_player INTEGER NOT NULL,
+        score INTEGER NOT NULL,
+        PRIMARY KEY (session_id, player_id)
+    );
+    CREATE TABLE player_answers (
+        session_id TEXT NOT NULL REFERENCES game_sessions (id),
+        player_id TEXT NOT NULL,
+        question_index INTEGER NOT NULL,
+        -- JSON array of the picked answer indexes
+        answers TEXT NOT NULL,
+        time INTEGER NOT NULL,
+        correct INTEGER NOT NULL,
+        points INTEGER NOT NULL
+    );
+    CREATE INDEX player_answers_session ON player_answers (session_id);
+    CREATE TABLE users (
+        id TEXT PRIMARY KEY,
+        nickname TEXT NOT NULL
+    );
+"#];
+
+/// A single SQLite database file. Queries run on the blocking thread pool.
+pub struct SqliteRepository {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteRepository {
+    /// Opens or creates the database, bringing its schema up to date.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let connection = Connection::open(path).map_err(StorageError::Sqlite)?;
+        SqliteRepository::with_connection(connection)
+    }
+
+    /// A database that only lives as long as the repository.
+    pub fn in_memory() -> Result<Self, StorageError> {
+        let connection = Connection::open_in_memory().map_err(StorageError::Sqlite)?;
+        SqliteRepository::with_connection(connection)
+    }
+
+    fn with_connection(mut connection: Connection) -> Result<Self, StorageError> {
+        connection
+            .pragma_update(None, "foreign_keys", true)
+            .map_err(StorageError::Sqlite)?;
+        migrate(&mut connection)?;
+        Ok(SqliteRepository {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    /// Stores a game session along with its template, replacing any session with the same id.
+    pub async fn insert_session(&self, session: StoredGameSession) -> Result<(), StorageError> {
+        self.run(move |connection| {
+            let transaction = connection.transaction().map_err(StorageError::Sqlite)?;
+            insert_session(&transaction, session)?;
+            transaction.commit().map_err(StorageError::Sqlite)
+        })
+        .await
+    }
+
+    /// Adds the game sessions and users from a JSON file in the memory store's format, which is how sessions get into
+    /// the database. Sessions that are already stored are left alone, so importing the same file again doesn't lose
+    /// their results.
+    ///
+    /// Returns the number of sessions that were added.
+    pub async fn import(&self, file: PathBuf) -> Result<usize, StorageError> {
+        let contents = tokio::fs::read_to_string(&file)
+            .await
+            .map_err(StorageError::Io)?;
+        let data: StoredData = serde_json::from_str(&contents).map_err(StorageError::Json)?;
+        self.run(move |connection| {
+            let transaction = connection.transaction().map_err(StorageError::Sqlite)?;
+            let mut imported = 0;
+            for session in data.game_sessions {
+                let exists = transaction
+                    .query_row(
+                        "SELECT 1 FROM game_sessions WHERE id = ?1",
+                        params![session.get_id()],
+                        |_| Ok(()),
+                    )
+                    .optional()
+                    .map_err(StorageError::Sqlite)?
+                    .is_some();
+                if !exists {
+                    insert_session(&transaction, session)?;
+                    imported += 1;
+                }
+            }
+            for user in data.users {
+                transaction
+                    .execute(
+                        "INSERT OR REPLACE INTO users (id, nickname) VALUES (?1, ?2)",
+                        params![user._id, user.nickname],
+                    )
+                    .map_err(StorageError::Sqlite)?;
+            }
+            transaction.commit().map_err(StorageError::Sqlite)?;
+            Ok(imported)
+        })
+        .await
+    }
+
+    pub async fn insert_user(&self, user_id: String, nickname: String) -> Result<(), StorageError> {
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "INSERT OR REPLACE INTO users (id, nickname) VALUES (?1, ?2)",
+                    params![user_id, nickname],
+                )
+                .map_err(StorageError::Sqlite)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Runs queries on the blocking thread pool so they don't hold up the async runtime.
+    async fn run<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&mut Connection) -> Result<T, StorageError> + Send + 'static,
+    ) -> Result<T, StorageError> {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || query(&mut connection.lock().unwrap()))
+            .await
+            .expect("SQLite query panicked")
+    }
+}
+
+/// Applies the migrations the database doesn't have yet.
+fn migrate(connection: &mut Connection) -> Result<(), StorageError> {
+    let version: usize = connection
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(StorageError::Sqlite)?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction().map_err(StorageError::Sqlite)?;
+        transaction
+            .execute_batch(migration)
+            .map_err(StorageError::Sqlite)?;
+        transaction
+            .pragma_update(None, "user_version", index + 1)
+            .map_err(StorageError::Sqlite)?;
+        transaction.commit().map_err(StorageError::Sqlite)?;
+    }
+    Ok(())
+}
+
+/// Stores a game session with its own copy of the template, replacing any session with the same id.
+fn insert_session(
+    transaction: &Transaction,
+    session: StoredGameSession,
+) -> Result<(), StorageError> {
+    let StoredGameSession {
+        _id: session_id,
+        code,
+        active,
+        host,
+        players,
+        template,
+        player_answers,
+    } = session;
+    let data = serde_json::to_string(&template).map_err(StorageError::Json)?;
+    transaction
+        .execute(
+            "INSERT OR REPLACE INTO game_sessions (id, code, active, host, template_id, template)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![session_id, code, active, host, template._id, data],
+        )
+        .map_err(StorageError::Sqlite)?;
+    replace_results(transaction, &session_id, &players, &player_answers)
+}
+
+/// Replaces the players and answers stored for a session.
+fn replace_results(
+    transaction: &Transaction,
+    session_id: &str,
+    players: &[StoredPlayer],
+    player_answers: &[StoredPlayerAnswers],
+) -> Result<(), StorageError> {
+    transaction
+        .execute(
+            "DELETE FROM session_players WHERE session_id = ?1",
+            params![session_id],
+        )
+        .map_err(StorageError::Sqlite)?;
+    transaction
+        .execute(
+            "DELETE FROM player_answers WHERE session_id = ?1",
+            params![session_id],
+        )
+        .map_err(StorageError::Sqlite)?;
+
+    let mut insert_player = transaction
+        .prepare(
+            "INSERT INTO session_players (session_id, player_id, nickname, registered_player, score)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .map_err(StorageError::Sqlite)?;
+    for player in players {
+        insert_player
+            .execute(params![
+                session_id,
+                player.player_id,
+                player.nickname,
+                player.registered_player,
+                player.score,
+            ])
+            .map_err(StorageError::Sqlite)?;
+    }
+
+    let mut insert_answer = transaction
+        .prepare(
+            "INSERT INTO player_answers (session_id, player_id, question_index, answers, time, correct, points)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .map_err(StorageError::Sqlite)?;
+    for answer in player_answers {
+        let answers = serde_json::to_string(&answer.answers).map_err(StorageError::Json)?;
+        insert_answer
+            .execute(params![
+                session_id,
+                answer.player_id,
+                answer.question_index,
+                answers,
+                answer.time,
+                answer.correct,
+                answer.points,
+            ])
+            .map_err(StorageError::Sqlite)?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl GameRepository for SqliteRepository {
+    async fn load_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<StoredGameSession>, StorageError> {
+        let session_id = session_id.to_string();
+        self.run(move |connection| {
+            let session = connection
+                .query_row(
+                    "SELECT code, active, host, template FROM game_sessions WHERE id = ?1",
+                    params![session_id],
+                    |row| {
+                        Ok((
+                            row.get::<_, u32>(0)?,
+                            row.get::<_, bool>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, String>(3)?,
+                        ))
+                    },
+                )
+                .optional()
+                .map_err(StorageError::Sqlite)?;
+            let Some((code, active, host, template)) = session else {
+                return Ok(None);
+            };
+            let template: StoredGameTemplate =
+                serde_json::from_str(&template).map_err(StorageError::Json)?;
+
+            let mut players = connection
+                .prepare(
+                    "SELECT player_id, nickname, registered_player, score
+                     FROM session_players WHERE session_id = ?1",
+                )
+                .map_err(StorageError::Sqlite)?;
+            let players = players
+                .query_map(params![session_id], |row| {
+                    Ok(StoredPlayer {
+                        player_id: row.get(0)?,
+                        nickname: row.get(1)?,
+                        registered_player: row.get(2)?,
+                        score: row.get(3)?,
+                    })
+                })
+                .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+                .map_err(StorageError::Sqlite)?;
+
+            let mut player_answers = connection
+                .prepare(
+                    "SELECT player_id, question_index, answers, time, correct, points
+                     FROM player_answers WHERE session_id = ?1 ORDER BY rowid",
+                )
+                .map_err(StorageError::Sqlite)?;
+            let player_answers = player_answers
+                .query_map(params![session_id], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, u32>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, u32>(3)?,
+                        row.get::<_, bool>(4)?,
+                        row.get::<_, i32>(5)?,
+                    ))
+                })
+                .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+                .map_err(StorageError::Sqlite)?;
+            let player_answers = player_answers
+                .into_iter()
+                .map(|(player_id, question_index, answers, time, correct, points)| {
+                    Ok(StoredPlayerAnswers {
+                        player_id,
+                        question_index,
+                        answers: serde_json::from_str(&answers).map_err(StorageError::Json)?,
+                        time,
+                        correct,
+                        points,
+                    })
+                })
+                .collect::<Result<Vec<_>, StorageError>>()?;
+
+            Ok(Some(StoredGameSession {
+                _id: session_id,
+                code,
+                active,
+                host,
+                players,
+                template,
+                player_answers,
+            }))
+        })
+        .await
+    }
+
+    async fn save_results(
+        &self,
+        session_id: &str,
+        players: &[StoredPlayer],
+        player_answers: &[StoredPlayerAnswers],
+    ) -> Result<(), StorageError> {
+        let session_id = session_id.to_string();
+        let (players, player_answers) = (players.to_vec(), player_answers.to_vec());
+        self.run(move |connection| {
+            let exists = connection
+                .query_row(
+                    "SELECT 1 FROM game_sessions WHERE id = ?1",
+                    params![session_id],
+                    |_| Ok(()),
+                )
+                .optional()
+                .map_err(StorageError::Sqlite)?
+                .is_some();
+            if !exists {
+                return Ok(());
+            }
+            let transaction = connection.transaction().map_err(StorageError::Sqlite)?;
+            replace_results(&transaction, &session_id, &players, &player_answers)?;
+            transaction.commit().map_err(StorageError::Sqlite)
+        })
+        .await
+    }
+
+    async fn mark_inactive(&self, session_id: &str) -> Result<(), StorageError> {
+        let session_id = session_id.to_string();
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "UPDATE game_sessions SET active = 0 WHERE id = ?1",
+                    params![session_id],
+                )
+                .map_err(StorageError::Sqlite)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn user_nickname(&self, user_id: &str) -> Result<Option<String>, StorageError> {
+        let user_id = user_id.to_string();
+        self.run(move |connection| {
+            connection
+                .query_row(
+                    "SELECT nickname FROM users WHERE id = ?1",
+                    params![user_id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(StorageError::Sqlite)
+        })
+        .await
+    }
+}
@@ -0,0 +1,67 @@
+//! Nicknames players show up with in games.
+
+use std::fmt;
+
+/// The longest nickname a guest can pick, in characters.
+pub const MAX_NICKNAME_LENGTH: usize = 24;
+
+/// Reasons a guest's nickname was rejected.
+#[derive(Debug, PartialEq)]
+pub enum NicknameError {
+    Empty,
+    TooLong,
+    InvalidCharacter,
+}
+
+impl fmt::Display for NicknameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NicknameError::Empty => write!(f, "nickname can't be empty"),
+            NicknameError::TooLong => {
+                write!(
+                    f,
+                    "nickname can be at most {MAX_NICKNAME_LENGTH} characters"
+                )
+            }
+            NicknameError::InvalidCharacter => write!(f, "nickname contains invalid characters"),
+        }
+    }
+}
+
+impl std::error::Error for NicknameError {}
+
+/// Checks a free-form nickname picked by a guest, returning it without surrounding whitespace.
+pub fn validate_nickname(nickname: &str) -> Result<String, NicknameError> {
+    let nickname = nickname.trim();
+    if nickname.is_empty() {
+        return Err(NicknameError::Empty);
+    }
+    if nickname.chars().count() > MAX_NICKNAME_LENGTH {
+        return Err(NicknameError::TooLong);
+    }
+    if nickname.chars().any(char::is_control) {
+        return Err(NicknameError::InvalidCharacter);
+    }
+    Ok(nickname.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nickname_validation() {
+        assert_eq!(Ok("Guest".to_string()), validate_nickname("  Guest "));
+        assert_eq!(Ok("Jänis 🐇".to_string()), validate_nickname("Jänis 🐇"));
+        assert_eq!(Err(NicknameError::Empty), validate_nickname("   "));
+        assert_eq!(
+            Err(NicknameError::TooLong),
+            validate_nickname(&"a".repeat(MAX_NICKNAME_LENGTH + 1))
+        );
+        assert!(validate_nickname(&"ä".repeat(MAX_NICKNAME_LENGTH)).is_ok());
+        assert_eq!(
+            Err(NicknameError::InvalidCharacter),
+            validate_nickname("new\nline")
+        );
+    }
+}